@@ -0,0 +1,222 @@
+use axum::{Router, extract::DefaultBodyLimit, http::header, middleware};
+use tower_http::{
+    cors::{Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        ContentBuilder, Ref, RefOr, ResponseBuilder,
+        security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
+    },
+};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    auth::{self, middleware::require_scope, models::Scope},
+    dead_letter,
+    error::{self, ErrorCode, PROBLEM_CONTENT_TYPE, ProblemDetails},
+    message, outbox,
+    products::{self},
+    state::AppState,
+    validation::FieldError,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "This project is a simple yet complete API built in Rust, demonstrating user authentication and full CRUD operations for products. It integrates MongoDB for persistent storage and uses Kafka to stream product-related events. The entire application is containerized with Docker for easy deployment, and Swagger UI is included to provide a clear and interactive interface for testing the API endpoints."),
+    modifiers(&SecurityAddon),
+    components(schemas(ProblemDetails, ErrorCode, FieldError)),
+    tags(
+        (name = "product", description = "product api management"),
+        (name = "message", description = "message api management"),
+        (name = "outbox", description = "event outbox monitoring"),
+        (name = "dead-letter", description = "dead-lettered event management"),
+        (name = "user", description = "user api management")
+    )
+)]
+pub struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "X-API-Key",
+                    "Personal API key from POST /auth/api-keys",
+                ))),
+            );
+        }
+    }
+}
+
+/// Documents the problem+json body as the default response of every
+/// operation, so clients know the shape of any error status. Applied after the
+/// routes are collected, since `ApiDoc` itself has no paths.
+struct ProblemResponseAddon;
+
+impl Modify for ProblemResponseAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let problem = ResponseBuilder::new()
+            .description("Error, as RFC 7807 problem details")
+            .content(
+                PROBLEM_CONTENT_TYPE,
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name("ProblemDetails")))
+                    .build(),
+            )
+            .build();
+        if let Some(components) = openapi.components.as_mut() {
+            components
+                .responses
+                .insert("Problem".to_string(), RefOr::T(problem));
+        }
+
+        for path_item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut path_item.get,
+                &mut path_item.post,
+                &mut path_item.put,
+                &mut path_item.patch,
+                &mut path_item.delete,
+            ];
+            for operation in operations.into_iter().flatten() {
+                operation.responses.responses.insert(
+                    "default".to_string(),
+                    RefOr::Ref(Ref::from_response_name("Problem")),
+                );
+            }
+        }
+    }
+}
+
+/// Builds the full API router together with its OpenAPI document. The storage
+/// and messaging backends are whatever `app_state` was built with.
+pub fn build_router(app_state: AppState) -> (Router, utoipa::openapi::OpenApi) {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([header::ETAG]);
+
+    let (router, mut api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .nest("/products", product_routes(app_state.clone()))
+        .nest("/messages", message_routes(app_state.clone()))
+        .nest("/outbox", outbox_routes(app_state.clone()))
+        .nest("/dead-letters", dead_letter_routes(app_state.clone()))
+        .nest("/users", user_routes(app_state.clone()))
+        .nest("/auth", account_routes(app_state.clone()))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth::middleware::auth_middleware,
+        ))
+        .nest("/auth", auth_routes(app_state.clone()))
+        .merge(well_known_routes(app_state.clone()))
+        .layer(middleware::from_fn(error::problem_details_middleware))
+        .layer(TraceLayer::new_for_http())
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(cors)
+        .split_for_parts();
+    ProblemResponseAddon.modify(&mut api);
+    (router, api)
+}
+
+fn auth_routes(app_state: AppState) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(auth::handlers::signup))
+        .routes(routes!(auth::handlers::login))
+        .routes(routes!(auth::handlers::refresh))
+        .routes(routes!(auth::handlers::logout))
+        .routes(routes!(auth::handlers::forgot_password))
+        .routes(routes!(auth::handlers::reset_password))
+        .routes(routes!(auth::handlers::verify_email))
+        .with_state(app_state)
+}
+
+/// `/auth` endpoints that act on the signed-in user, behind `auth_middleware`.
+fn account_routes(app_state: AppState) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(
+            auth::handlers::get_me,
+            auth::handlers::update_me,
+            auth::handlers::delete_me
+        ))
+        .routes(routes!(auth::handlers::change_password))
+        .routes(routes!(
+            auth::handlers::create_api_key,
+            auth::handlers::list_api_keys
+        ))
+        .routes(routes!(auth::handlers::revoke_api_key))
+        .with_state(app_state)
+}
+
+fn well_known_routes(app_state: AppState) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(auth::handlers::jwks))
+        .with_state(app_state)
+}
+
+/// Restricts every route added so far to callers whose role grants `scope`.
+fn require(router: OpenApiRouter<AppState>, scope: Scope) -> OpenApiRouter<AppState> {
+    router.route_layer(middleware::from_fn_with_state(scope, require_scope))
+}
+
+fn product_routes(app_state: AppState) -> OpenApiRouter {
+    let reads = OpenApiRouter::new()
+        .routes(routes!(products::handlers::list_products))
+        .routes(routes!(products::handlers::search_products))
+        .routes(routes!(products::handlers::export_products))
+        .routes(routes!(products::handlers::get_product));
+    let writes = OpenApiRouter::new()
+        .routes(routes!(products::handlers::create_product))
+        .routes(routes!(products::handlers::bulk_products))
+        .routes(routes!(
+            products::handlers::delete_product,
+            products::handlers::update_product,
+        ))
+        .routes(routes!(products::handlers::list_trash))
+        .routes(routes!(products::handlers::restore_product))
+        .routes(routes!(products::handlers::get_import_job));
+    // Uploads get their own size limit instead of the 2 MB default.
+    let imports = OpenApiRouter::new()
+        .routes(routes!(products::handlers::import_products))
+        .layer(DefaultBodyLimit::max(app_state.config.product_import_max_bytes));
+    let writes = writes.merge(imports);
+
+    require(reads, Scope::ProductsRead)
+        .merge(require(writes, Scope::ProductsWrite))
+        .with_state(app_state)
+}
+
+fn message_routes(app_state: AppState) -> OpenApiRouter {
+    let routes = OpenApiRouter::new().routes(routes!(message::handlers::list_messages));
+    require(routes, Scope::MessagesRead).with_state(app_state)
+}
+
+fn outbox_routes(app_state: AppState) -> OpenApiRouter {
+    let routes = OpenApiRouter::new().routes(routes!(outbox::handlers::outbox_stats));
+    require(routes, Scope::Admin).with_state(app_state)
+}
+
+fn dead_letter_routes(app_state: AppState) -> OpenApiRouter {
+    let routes = OpenApiRouter::new()
+        .routes(routes!(dead_letter::handlers::list_dead_letters))
+        .routes(routes!(dead_letter::handlers::redrive_dead_letter));
+    require(routes, Scope::Admin).with_state(app_state)
+}
+
+fn user_routes(app_state: AppState) -> OpenApiRouter {
+    let routes = OpenApiRouter::new()
+        .routes(routes!(auth::handlers::set_user_role))
+        .routes(routes!(auth::handlers::unlock_user));
+    require(routes, Scope::Admin).with_state(app_state)
+}
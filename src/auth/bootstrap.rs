@@ -0,0 +1,43 @@
+use tracing::{info, warn};
+
+use crate::{
+    auth::{
+        models::{Role, User},
+        utils::hash_password,
+    },
+    config::Config,
+    db::repository::Repository,
+};
+
+/// Makes sure the account named by `BOOTSTRAP_ADMIN_USERNAME` exists and is an
+/// admin, so a fresh deployment has someone who can grant roles. Safe to run
+/// on every boot; an existing password is never changed.
+pub async fn ensure_bootstrap_admin(
+    db_repo: &dyn Repository,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(username) = config.bootstrap_admin_username.as_deref() else {
+        return Ok(());
+    };
+
+    match db_repo.find_user_by_username(username).await? {
+        Some(user) if user.role == Role::Admin => {}
+        Some(_) => {
+            db_repo.update_user_role(username, Role::Admin).await?;
+            warn!("Promoted existing user '{}' to admin", username);
+        }
+        None => {
+            let Some(password) = config.bootstrap_admin_password.as_deref() else {
+                return Err(format!(
+                    "BOOTSTRAP_ADMIN_PASSWORD is required to create admin '{}'",
+                    username
+                )
+                .into());
+            };
+            let admin = User::new(username.to_string(), hash_password(password)?, Role::Admin);
+            db_repo.create_user(admin).await?;
+            info!("Created bootstrap admin '{}'", username);
+        }
+    }
+    Ok(())
+}
@@ -1,80 +1,120 @@
 use crate::{
   auth::{
-      models::{AuthResponse, LoginRequest, SignupRequest, User},
-      utils::{create_jwt, hash_password, verify_password},
+      keys::JwkSet,
+      middleware::extract_token,
+      models::{
+          AccountToken, ApiKey, ApiKeyResponse, AuthMethod, AuthResponse, ChangePasswordRequest,
+          CreateApiKeyRequest, CreatedApiKeyResponse, ForgotPasswordRequest, LoginRequest,
+          RefreshRequest, RefreshToken, ResetPasswordRequest, RevokedToken, SignupRequest,
+          TokenPurpose, UpdateProfileRequest, UpdateRoleRequest, User, UserId, UserResponse,
+          VerifyEmailRequest,
+      },
+      throttle::ClientIp,
+      utils::{
+          create_jwt, decode_jwt, generate_token, hash_password, hash_token,
+          verify_password,
+      },
   },
+  db::mongo::MongoError,
+  error::{AppError, ProblemDetails},
+  kafka::{
+      envelope::EventContext,
+      producer::{UserEvent, UserEventType},
+  },
+  mail::models::OutgoingEmail,
+  outbox::models::OutboxEntry,
   state::AppState,
+  validation::ValidatedJson,
+};
+use axum::{
+  extract::{Path, State},
+  Extension,
+  http::{header, HeaderMap, StatusCode},
+  response::IntoResponse,
+  Json,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use mongodb::bson::oid::ObjectId;
+use chrono::{DateTime, Duration, Utc};
+use mongodb::bson::{oid::ObjectId, Document};
 use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Issues an access token plus a refresh token in `family_id`, the chain of
+/// refresh tokens descending from a single login.
+async fn issue_tokens(
+  state: &AppState,
+  user: &User,
+  family_id: String,
+) -> Result<AuthResponse, AppError> {
+  let user_id = user._id.expect("User from DB should have an ID");
+  let token = create_jwt(
+      &user_id.to_hex(),
+      user.role,
+      user.token_epoch,
+      &state.config,
+      &state.jwt_keys,
+  )?;
+
+  let refresh_token = generate_token();
+  let now = Utc::now();
+  state
+      .db_repo
+      .create_refresh_token(RefreshToken {
+          _id: None,
+          user_id,
+          family_id,
+          token_hash: hash_token(&refresh_token),
+          created_at: now,
+          expires_at: now + Duration::days(state.config.refresh_token_ttl_days as i64),
+          used_at: None,
+          revoked_at: None,
+      })
+      .await?;
+
+  Ok(AuthResponse {
+      token,
+      token_type: "Bearer".to_string(),
+      expires_in: state.config.access_token_ttl_minutes * 60,
+      refresh_token,
+  })
+}
 
 #[utoipa::path(
     post,
     path = "/signup",
     tag = "user",
+    request_body = SignupRequest,
     responses(
-        (status = 200, description = "Signup successfully")
+        (status = 201, description = "Signup successfully"),
+        (status = 422, description = "Username or password breaks validation rules", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Username already exists", body = ProblemDetails, content_type = "application/problem+json")
     ),
 )]
 pub async fn signup(
   State(state): State<AppState>,
-  Json(payload): Json<SignupRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-  if payload.username.is_empty() || payload.password.is_empty() {
-      return Err((
-          StatusCode::BAD_REQUEST,
-          "Username and password cannot be empty".to_string(),
-      ));
-  }
-
-  match state.db_repo.find_user_by_username(&payload.username).await {
-      Ok(Some(_)) => {
-          warn!("Signup attempt with existing username: {}", payload.username);
-          return Err((
-              StatusCode::CONFLICT,
-              "Username already exists".to_string(),
-          ));
-      }
-      Ok(None) => {}
-      Err(e) => {
-          error!("Database error checking username: {:?}", e);
-          return Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "Failed to check username availability".to_string(),
-          ));
-      }
-  }
-
-  let password_hash = match hash_password(&payload.password) {
-      Ok(hash) => hash,
-      Err(e) => {
-          error!("Failed to hash password: {:?}", e);
-          return Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "Failed to process registration".to_string(),
-          ));
-      }
-  };
+  ValidatedJson(payload): ValidatedJson<SignupRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  let password_hash = hash_password(&payload.password)?;
 
-  let new_user = User {
-      _id: Some(ObjectId::new()),
-      username: payload.username.clone(),
-      password_hash,
-  };
+  let new_user = User::new(payload.username.clone(), password_hash, state.config.signup_role);
 
   match state.db_repo.create_user(new_user).await {
       Ok(user_id) => {
           info!("New user created with ID: {}", user_id);
           Ok((StatusCode::CREATED, "User created successfully".to_string()))
       }
-      Err(e) => {
-          error!("Failed to create user in database: {:?}", e);
-          Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "Failed to register user".to_string(),
-          ))
+      // The unique username index settles concurrent signups for the same name.
+      Err(MongoError::DuplicateKey(username)) => {
+          warn!("Signup attempt with existing username: {}", username);
+          Err(AppError::Conflict("Username already exists".to_string()))
       }
+      Err(e) => Err(e.into()),
+  }
+}
+
+fn account_locked(locked_until: DateTime<Utc>, now: DateTime<Utc>) -> AppError {
+  AppError::TooManyRequests {
+      detail: "Account is temporarily locked after repeated failed logins".to_string(),
+      retry_after: (locked_until - now).num_seconds().max(1) as u64,
   }
 }
 
@@ -83,59 +123,801 @@ pub async fn signup(
     path = "/login",
     tag = "user",
     responses(
-        (status = 200, description = "Login successfully", body = AuthResponse)
+        (status = 200, description = "Login successfully", body = AuthResponse),
+        (status = 401, description = "Invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Account locked or too many failed logins from this address; see `Retry-After`", body = ProblemDetails, content_type = "application/problem+json")
     ),
 )]
 pub async fn login(
   State(state): State<AppState>,
+  ClientIp(ip): ClientIp,
   Json(payload): Json<LoginRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-  let user = match state.db_repo.find_user_by_username(&payload.username).await {
-      Ok(Some(user)) => user,
-      Ok(None) => {
-          warn!("Login attempt for non-existent user: {}", payload.username);
-          return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+) -> Result<impl IntoResponse, AppError> {
+  let throttle = &state.login_throttle;
+  let policy = throttle.policy();
+  if let Some(retry_after) = throttle.ip_blocked_for(ip) {
+      warn!("SECURITY: login from {} refused, too many failed attempts", ip);
+      return Err(AppError::TooManyRequests {
+          detail: "Too many failed login attempts, try again later".to_string(),
+          retry_after,
+      });
+  }
+
+  let now = Utc::now();
+  // A username without an account is delayed, counted and locked like one
+  // with, so neither the delay nor the lockout tells them apart.
+  let Some(user) = state.db_repo.find_user_by_username(&payload.username).await? else {
+      if let Some(locked_until) = throttle.unknown_user_locked_until(&payload.username, now) {
+          warn!(
+              "SECURITY: login for locked non-existent user '{}' from {} refused",
+              payload.username, ip
+          );
+          return Err(account_locked(locked_until, now));
       }
-      Err(e) => {
-          error!("Database error during login: {:?}", e);
-          return Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "Login failed".to_string(),
-          ));
+      let failures = throttle
+          .unknown_user_failures(&payload.username, now)
+          .max(throttle.ip_failures(ip));
+      tokio::time::sleep(policy.delay(failures)).await;
+      throttle.record_ip_failure(ip);
+      let failures = throttle.record_unknown_user_failure(&payload.username, now);
+      warn!(
+          "Login attempt for non-existent user: {} from {} ({} recent failures)",
+          payload.username, ip, failures
+      );
+      return Err(AppError::Unauthorized("Invalid credentials".to_string()));
+  };
+  let user_id = user._id.expect("User from DB should have an ID");
+
+  if let Some(locked_until) = user.locked_until.filter(|until| *until > now) {
+      warn!("SECURITY: login for locked account '{}' from {} refused", user.username, ip);
+      return Err(account_locked(locked_until, now));
+  }
+
+  // Hold back before the bcrypt check, so guessing gets slower with every
+  // miss whether it targets one account or sweeps from one address.
+  let failures = policy
+      .recent_failures(&user, now)
+      .max(throttle.ip_failures(ip));
+  tokio::time::sleep(policy.delay(failures)).await;
+
+  if !verify_password(&payload.password, &user.password_hash)? {
+      throttle.record_ip_failure(ip);
+      let failures = state
+          .db_repo
+          .record_failed_login(user_id, now, policy.window_start(now))
+          .await?
+          .map_or(0, |user| user.failed_login_count);
+      warn!(
+          "Incorrect password attempt for user: {} from {} ({} recent failures)",
+          payload.username, ip, failures
+      );
+      if failures >= policy.max_failures {
+          let until = now + Duration::from_std(policy.lockout).unwrap_or_default();
+          state.db_repo.lock_user(user_id, until).await?;
+          warn!(
+              "SECURITY: locked account '{}' until {} after {} failed logins (last from {})",
+              user.username, until, failures, ip
+          );
       }
+      return Err(AppError::Unauthorized("Invalid credentials".to_string()));
+  }
+
+  if user.failed_login_count > 0 || user.locked_until.is_some() {
+      state.db_repo.reset_failed_logins(user_id).await?;
+      if user.locked_until.is_some() {
+          info!(
+              "SECURITY: lockout of account '{}' expired, login succeeded from {}",
+              user.username, ip
+          );
+      }
+  }
+
+  let response = issue_tokens(&state, &user, Uuid::new_v4().to_string()).await?;
+  info!("User logged in successfully: {}", payload.username);
+  Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/refresh",
+    tag = "user",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "Tokens rotated; the old refresh token is no longer valid", body = AuthResponse),
+        (status = 401, description = "Refresh token is unknown, expired, revoked or already used", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+)]
+pub async fn refresh(
+  State(state): State<AppState>,
+  Json(payload): Json<RefreshRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  let invalid = || AppError::Unauthorized("Invalid refresh token".to_string());
+  let token_hash = hash_token(&payload.refresh_token);
+  let Some(stored) = state.db_repo.find_refresh_token_by_hash(&token_hash).await? else {
+      warn!("Refresh attempt with unknown token");
+      return Err(invalid());
+  };
+  let token_id = stored._id.expect("Refresh token from DB should have an ID");
+
+  if stored.expires_at <= Utc::now() {
+      return Err(AppError::Unauthorized("Refresh token has expired".to_string()));
+  }
+
+  // A token that was already rotated or revoked being presented again means
+  // it leaked: shut down the whole family so neither party can keep using it.
+  if !state.db_repo.claim_refresh_token(token_id).await? {
+      let revoked = state.db_repo.revoke_refresh_token_family(&stored.family_id).await?;
+      warn!(
+          "SECURITY: refresh token reuse detected for user {} (family {}); revoked {} tokens",
+          stored.user_id, stored.family_id, revoked
+      );
+      return Err(invalid());
+  }
+
+  // Re-read the user so role changes take effect on the next rotation.
+  let Some(user) = state.db_repo.find_user_by_id(stored.user_id).await? else {
+      state.db_repo.revoke_refresh_token_family(&stored.family_id).await?;
+      return Err(invalid());
+  };
+
+  let response = issue_tokens(&state, &user, stored.family_id).await?;
+  info!("Refreshed tokens for user: {}", user.username);
+  Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "user",
+    request_body = RefreshRequest,
+    responses(
+        (status = 204, description = "Refresh token family revoked; the bearer access token, if sent, is revoked too")
+    ),
+    security(
+        (),
+        ("token" = []),
+        ("api_key" = [])
+    )
+)]
+pub async fn logout(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  Json(payload): Json<RefreshRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  // Logout is idempotent: unknown or already revoked tokens are not an error.
+  let token_hash = hash_token(&payload.refresh_token);
+  if let Some(stored) = state.db_repo.find_refresh_token_by_hash(&token_hash).await? {
+      state.db_repo.revoke_refresh_token_family(&stored.family_id).await?;
+      info!("User {} logged out (family {})", stored.user_id, stored.family_id);
+  }
+  revoke_bearer_token(&state, &headers).await?;
+
+  Ok(StatusCode::NO_CONTENT)
+}
+
+/// Denylists the access token the request was made with, if it is valid.
+async fn revoke_bearer_token(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+  let claims = extract_token(headers).and_then(|token| decode_jwt(&token, &state.jwt_keys).ok());
+  if let Some(claims) = claims.filter(|claims| !claims.jti.is_empty()) {
+      let expires_at = DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(Utc::now);
+      state
+          .db_repo
+          .revoke_access_token(RevokedToken { jti: claims.jti, expires_at })
+          .await?;
+  }
+  Ok(())
+}
+
+/// API keys act for their owner but may not manage the account itself.
+fn require_session(auth_method: AuthMethod, action: &str) -> Result<(), AppError> {
+  if auth_method == AuthMethod::ApiKey {
+      return Err(AppError::Forbidden(format!(
+          "API keys cannot {}; sign in instead",
+          action
+      )));
+  }
+  Ok(())
+}
+
+fn user_event_entry<T: serde::Serialize>(
+  state: &AppState,
+  event_context: &EventContext,
+  event_type: UserEventType,
+  user_id: ObjectId,
+  payload: Option<T>,
+) -> Result<OutboxEntry, serde_json::Error> {
+  let event = UserEvent::new(
+      event_context,
+      &state.config.event_source,
+      event_type,
+      user_id.to_hex(),
+      payload,
+  );
+  OutboxEntry::for_user_event(&state.config.kafka_user_events_topic, &event)
+}
+
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "user",
+    responses(
+        (status = 200, description = "Profile of the authenticated user", body = UserResponse),
+        (status = 404, description = "Account no longer exists", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("token" = []),
+        ("api_key" = [])
+    )
+)]
+pub async fn get_me(
+  State(state): State<AppState>,
+  user_id: UserId,
+) -> Result<impl IntoResponse, AppError> {
+  let Some(user) = state.db_repo.find_user_by_id(user_id.object_id()?).await? else {
+      return Err(AppError::NotFound("User not found".to_string()));
   };
+  Ok((StatusCode::OK, Json(UserResponse::from_user(&user))))
+}
 
-  match verify_password(&payload.password, &user.password_hash) {
-      Ok(true) => {}
-      Ok(false) => {
-          warn!("Incorrect password attempt for user: {}", payload.username);
-          return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+#[utoipa::path(
+    patch,
+    path = "/me",
+    tag = "user",
+    request_body = UpdateProfileRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = UserResponse),
+        (status = 403, description = "Called with an API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Account no longer exists", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Body breaks validation rules", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
+    ),
+    security(
+        ("token" = [])
+    )
+)]
+pub async fn update_me(
+  State(state): State<AppState>,
+  user_id: UserId,
+  Extension(auth_method): Extension<AuthMethod>,
+  event_context: EventContext,
+  ValidatedJson(payload): ValidatedJson<UpdateProfileRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  // The email address is how an account is recovered, so an API key must not
+  // be able to change it.
+  require_session(auth_method, "update the profile")?;
+  let id = user_id.object_id()?;
+
+  let mut update = Document::new();
+  if let Some(display_name) = payload.display_name {
+      update.insert("display_name", display_name);
+  }
+  // A new address starts unverified. Sending the current, still unverified
+  // address again just mails a fresh verification link.
+  let mut verify_email = None;
+  if let Some(email) = payload.email {
+      let Some(current) = state.db_repo.find_user_by_id(id).await? else {
+          return Err(AppError::NotFound("User not found".to_string()));
+      };
+      if current.email.as_deref() != Some(email.as_str()) || !current.email_verified {
+          update.insert("email_verified", false);
+          verify_email = Some(email.clone());
       }
-      Err(e) => {
-          error!("Password verification error for user {}: {:?}", payload.username, e);
-          return Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "Login failed".to_string(),
-          ));
+      update.insert("email", email);
+  }
+  if update.is_empty() {
+      return get_me(State(state), user_id).await.map(IntoResponse::into_response);
+  }
+
+  let event_state = state.clone();
+  let event_factory = Box::new(move |user: &User| {
+      user_event_entry(
+          &event_state,
+          &event_context,
+          UserEventType::Updated,
+          id,
+          Some(UserResponse::from_user(user)),
+      )
+  });
+  let Some(user) = state.db_repo.update_user_profile(id, update, event_factory).await? else {
+      return Err(AppError::NotFound("User not found".to_string()));
+  };
+  info!("Profile updated for user: {}", user.username);
+  if let Some(email) = verify_email {
+      // The profile change is already saved; a mail failure only means the
+      // user has to ask for another link.
+      let sent = send_account_email(&state, &user, TokenPurpose::EmailVerification, &email).await;
+      if let Err(e) = sent {
+          error!("Failed to send verification email to user {}: {:?}", user.username, e);
       }
   }
+  Ok((StatusCode::OK, Json(UserResponse::from_user(&user))).into_response())
+}
 
-  let user_id = user._id.expect("User from DB should have an ID").to_hex();
-  match create_jwt(&user_id, &state.config) {
-      Ok(token) => {
-          info!("User logged in successfully: {}", payload.username);
-          let response = AuthResponse {
-              token,
-              token_type: "Bearer".to_string(),
-          };
-          Ok((StatusCode::OK, Json(response)))
+/// Issues a single-use token for `purpose` and mails its link to `email`.
+async fn send_account_email(
+  state: &AppState,
+  user: &User,
+  purpose: TokenPurpose,
+  email: &str,
+) -> Result<(), AppError> {
+  let config = &state.config;
+  let token = generate_token();
+  let now = Utc::now();
+  let ttl = match purpose {
+      TokenPurpose::PasswordReset => Duration::minutes(config.password_reset_ttl_minutes as i64),
+      TokenPurpose::EmailVerification => {
+          Duration::hours(config.email_verification_ttl_hours as i64)
       }
-      Err(e) => {
-          error!("Failed to create JWT for user {}: {:?}", payload.username, e);
-          Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "Login failed".to_string(),
-          ))
+  };
+  state
+      .db_repo
+      .create_account_token(AccountToken {
+          _id: None,
+          user_id: user._id.expect("User from DB should have an ID"),
+          purpose,
+          token_hash: hash_token(&token),
+          email: Some(email.to_string()),
+          created_at: now,
+          expires_at: now + ttl,
+          used_at: None,
+      })
+      .await?;
+
+  let base_url = config.public_base_url.trim_end_matches('/');
+  let message = match purpose {
+      TokenPurpose::PasswordReset => OutgoingEmail::new(
+          &config.mail_from,
+          email,
+          "Reset your password",
+          format!(
+              "Hi {},\n\nUse this link to choose a new password. It works once and expires in {} minutes:\n\n{}/reset-password?token={}\n\nIf you did not ask for a reset, ignore this email; your password is unchanged.\n",
+              user.username, config.password_reset_ttl_minutes, base_url, token
+          ),
+      ),
+      TokenPurpose::EmailVerification => OutgoingEmail::new(
+          &config.mail_from,
+          email,
+          "Verify your email address",
+          format!(
+              "Hi {},\n\nConfirm this address for your account with the link below. It expires in {} hours:\n\n{}/verify-email?token={}\n",
+              user.username, config.email_verification_ttl_hours, base_url, token
+          ),
+      ),
+  };
+  state.mailer.send(message).await?;
+  info!("Sent {} email to user {}", purpose.as_str(), user.username);
+  Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/forgot-password",
+    tag = "user",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 202, description = "If a verified account uses this address, a reset link was sent to it"),
+        (status = 422, description = "Not an email address", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+)]
+pub async fn forgot_password(
+  State(state): State<AppState>,
+  ValidatedJson(payload): ValidatedJson<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  // The response never says whether the address is known, so this cannot be
+  // used to find out who has an account.
+  let users = state.db_repo.find_users_by_email(&payload.email).await?;
+  for user in users.iter().filter(|user| user.email_verified) {
+      let sent = send_account_email(&state, user, TokenPurpose::PasswordReset, &payload.email).await;
+      if let Err(e) = sent {
+          error!("Failed to send password reset email to user {}: {:?}", user.username, e);
       }
   }
-}
\ No newline at end of file
+  Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/reset-password",
+    tag = "user",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 204, description = "Password replaced; every session and API key of the account is revoked and any lockout lifted"),
+        (status = 400, description = "Token is invalid, expired or already used", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "New password breaks validation rules", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
+    ),
+)]
+pub async fn reset_password(
+  State(state): State<AppState>,
+  event_context: EventContext,
+  ValidatedJson(payload): ValidatedJson<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  let invalid = || AppError::BadRequest("Invalid or expired token".to_string());
+  let token_hash = hash_token(&payload.token);
+  let Some(token) = state
+      .db_repo
+      .claim_account_token(&token_hash, TokenPurpose::PasswordReset, Utc::now())
+      .await?
+  else {
+      warn!("Password reset attempted with an invalid token");
+      return Err(invalid());
+  };
+
+  let id = token.user_id;
+  let password_hash = hash_password(&payload.new_password)?;
+  let event =
+      user_event_entry::<()>(&state, &event_context, UserEventType::PasswordChanged, id, None)?;
+  if !state.db_repo.update_user_password(id, password_hash, event).await? {
+      return Err(invalid());
+  }
+  // A reset may follow a compromise, so nothing issued before it survives:
+  // refresh tokens, API keys, and access tokens through the epoch.
+  let revoked = state.db_repo.revoke_user_refresh_tokens(id).await?;
+  let revoked_keys = state.db_repo.revoke_user_api_keys(id).await?;
+  state.db_repo.bump_token_epoch(id).await?;
+  state.db_repo.reset_failed_logins(id).await?;
+  warn!(
+      "SECURITY: password of user {} reset by email link; revoked {} refresh tokens and {} API keys",
+      id, revoked, revoked_keys
+  );
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/verify-email",
+    tag = "user",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 204, description = "Email address verified"),
+        (status = 400, description = "Token is invalid, expired, already used or for an address the account no longer has", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+)]
+pub async fn verify_email(
+  State(state): State<AppState>,
+  Json(payload): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  let token_hash = hash_token(&payload.token);
+  let token = state
+      .db_repo
+      .claim_account_token(&token_hash, TokenPurpose::EmailVerification, Utc::now())
+      .await?;
+  let Some(AccountToken { user_id, email: Some(email), .. }) = token else {
+      return Err(AppError::BadRequest("Invalid or expired token".to_string()));
+  };
+  if !state.db_repo.mark_email_verified(user_id, &email).await? {
+      return Err(AppError::BadRequest(
+          "This link is for an address the account no longer uses".to_string(),
+      ));
+  }
+  info!("Verified email address of user {}", user_id);
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/change-password",
+    tag = "user",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 204, description = "Password changed; every session of the account, including this one, is revoked while its API keys stay valid"),
+        (status = 403, description = "Current password is incorrect, or called with an API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "New password breaks validation rules", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
+    ),
+    security(
+        ("token" = [])
+    )
+)]
+pub async fn change_password(
+  State(state): State<AppState>,
+  user_id: UserId,
+  Extension(auth_method): Extension<AuthMethod>,
+  event_context: EventContext,
+  ValidatedJson(payload): ValidatedJson<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  require_session(auth_method, "change the password")?;
+  let id = user_id.object_id()?;
+  let Some(user) = state.db_repo.find_user_by_id(id).await? else {
+      return Err(AppError::NotFound("User not found".to_string()));
+  };
+  if !verify_password(&payload.current_password, &user.password_hash)? {
+      warn!("Incorrect current password on password change for user: {}", user.username);
+      return Err(AppError::Forbidden("Current password is incorrect".to_string()));
+  }
+
+  let password_hash = hash_password(&payload.new_password)?;
+  let event =
+      user_event_entry::<()>(&state, &event_context, UserEventType::PasswordChanged, id, None)?;
+  if !state.db_repo.update_user_password(id, password_hash, event).await? {
+      return Err(AppError::NotFound("User not found".to_string()));
+  }
+  // Anyone holding an old session has to log in again with the new password,
+  // so refresh tokens go and the epoch bump retires issued access tokens.
+  // API keys are kept: they are managed one by one under /api-keys, and only
+  // a reset, which may follow a compromise, takes them down wholesale.
+  let revoked = state.db_repo.revoke_user_refresh_tokens(id).await?;
+  state.db_repo.bump_token_epoch(id).await?;
+  info!("Password changed for user {}; revoked {} refresh tokens", user.username, revoked);
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/me",
+    tag = "user",
+    responses(
+        (status = 204, description = "Account deleted and all of its tokens revoked"),
+        (status = 403, description = "Called with an API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Account no longer exists", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
+    ),
+    security(
+        ("token" = [])
+    )
+)]
+pub async fn delete_me(
+  State(state): State<AppState>,
+  user_id: UserId,
+  Extension(auth_method): Extension<AuthMethod>,
+  event_context: EventContext,
+  headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+  require_session(auth_method, "delete the account")?;
+  let id = user_id.object_id()?;
+  let event = user_event_entry::<()>(&state, &event_context, UserEventType::Deleted, id, None)?;
+  if !state.db_repo.delete_user(id, event).await? {
+      return Err(AppError::NotFound("User not found".to_string()));
+  }
+  state.db_repo.revoke_user_refresh_tokens(id).await?;
+  revoke_bearer_token(&state, &headers).await?;
+  info!("User deleted their account: {}", user_id.0);
+  Ok(StatusCode::NO_CONTENT)
+}
+
+/// Marks personal API keys so a leaked one is easy to spot, e.g. by secret
+/// scanners.
+const API_KEY_PREFIX: &str = "rkm_";
+/// Characters of a key kept in clear, enough to tell keys apart in a listing.
+const API_KEY_DISPLAY_LEN: usize = 12;
+const API_KEY_DEFAULT_TTL_DAYS: u32 = 90;
+const MAX_API_KEYS_PER_USER: usize = 25;
+
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    tag = "user",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created; the key is only shown in this response", body = CreatedApiKeyResponse),
+        (status = 400, description = "No scopes requested", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Scope beyond the caller's role, or called with an API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Too many active API keys", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Body breaks validation rules", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("token" = [])
+    )
+)]
+pub async fn create_api_key(
+  State(state): State<AppState>,
+  user_id: UserId,
+  Extension(auth_method): Extension<AuthMethod>,
+  ValidatedJson(payload): ValidatedJson<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  // Otherwise a leaked key could mint fresh ones that outlive its revocation.
+  require_session(auth_method, "create API keys")?;
+  let id = user_id.object_id()?;
+  let Some(user) = state.db_repo.find_user_by_id(id).await? else {
+      return Err(AppError::NotFound("User not found".to_string()));
+  };
+
+  let mut scopes = Vec::new();
+  for scope in payload.scopes.unwrap_or_else(|| user.role.scopes().to_vec()) {
+      if !scopes.contains(&scope) {
+          scopes.push(scope);
+      }
+  }
+  if scopes.is_empty() {
+      return Err(AppError::Validation("scopes: at least one scope is required".to_string()));
+  }
+  if let Some(scope) = scopes.iter().find(|scope| !user.role.grants(**scope)) {
+      return Err(AppError::Forbidden(format!(
+          "Role '{}' cannot grant the '{}' scope",
+          user.role.as_str(),
+          scope.as_str()
+      )));
+  }
+
+  let now = Utc::now();
+  let active = state
+      .db_repo
+      .list_api_keys(id)
+      .await?
+      .iter()
+      .filter(|key| key.is_active(now))
+      .count();
+  if active >= MAX_API_KEYS_PER_USER {
+      return Err(AppError::Conflict(format!(
+          "At most {} active API keys are allowed; revoke one first",
+          MAX_API_KEYS_PER_USER
+      )));
+  }
+
+  let key = format!("{}{}", API_KEY_PREFIX, generate_token());
+  let ttl_days = payload.expires_in_days.unwrap_or(API_KEY_DEFAULT_TTL_DAYS);
+  let mut api_key = ApiKey {
+      _id: None,
+      user_id: id,
+      name: payload.name,
+      prefix: key[..API_KEY_DISPLAY_LEN].to_string(),
+      key_hash: hash_token(&key),
+      scopes,
+      created_at: now,
+      expires_at: now + Duration::days(i64::from(ttl_days)),
+      last_used_at: None,
+      revoked_at: None,
+  };
+  api_key._id = Some(state.db_repo.create_api_key(api_key.clone()).await?);
+  info!(
+      "SECURITY: API key '{}' ({}) created by user {}",
+      api_key.name, api_key.prefix, user.username
+  );
+
+  Ok((
+      StatusCode::CREATED,
+      Json(CreatedApiKeyResponse {
+          key,
+          api_key: ApiKeyResponse::from_api_key(&api_key),
+      }),
+  ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    tag = "user",
+    responses(
+        (status = 200, description = "The caller's API keys, newest first", body = [ApiKeyResponse])
+    ),
+    security(
+        ("token" = []),
+        ("api_key" = [])
+    )
+)]
+pub async fn list_api_keys(
+  State(state): State<AppState>,
+  user_id: UserId,
+) -> Result<impl IntoResponse, AppError> {
+  let keys = state.db_repo.list_api_keys(user_id.object_id()?).await?;
+  let keys: Vec<ApiKeyResponse> = keys.iter().map(ApiKeyResponse::from_api_key).collect();
+  Ok((StatusCode::OK, Json(keys)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{id}",
+    tag = "user",
+    responses(
+        (status = 204, description = "API key revoked"),
+        (status = 404, description = "No such active key for this user", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = String, Path, description = "API key id")
+    ),
+    security(
+        ("token" = []),
+        ("api_key" = [])
+    )
+)]
+pub async fn revoke_api_key(
+  State(state): State<AppState>,
+  user_id: UserId,
+  Path(key_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+  let not_found = || AppError::NotFound("API key not found".to_string());
+  let key_id: ObjectId = key_id.parse().map_err(|_| not_found())?;
+  if !state
+      .db_repo
+      .revoke_api_key(key_id, user_id.object_id()?)
+      .await?
+  {
+      return Err(not_found());
+  }
+  info!("SECURITY: API key {} revoked by user {}", key_id, user_id.0);
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/{username}/role",
+    tag = "user",
+    responses(
+        (status = 200, description = "Role updated successfully", body = UserResponse),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "User not found", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("username" = String, Path, description = "username")
+    ),
+    security(
+        ("token" = ["admin"]),
+        ("api_key" = ["admin"])
+    )
+)]
+pub async fn set_user_role(
+  State(state): State<AppState>,
+  Path(username): Path<String>,
+  Json(payload): Json<UpdateRoleRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  let Some(user) = state.db_repo.update_user_role(&username, payload.role).await? else {
+      return Err(AppError::NotFound("User not found".to_string()));
+  };
+  info!("User '{}' is now {}", username, payload.role.as_str());
+  Ok((StatusCode::OK, Json(UserResponse::from_user(&user))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/{username}/unlock",
+    tag = "user",
+    responses(
+        (status = 200, description = "Lockout and failed-login count cleared", body = UserResponse),
+        (status = 403, description = "Caller is not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "User not found", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("username" = String, Path, description = "username")
+    ),
+    security(
+        ("token" = ["admin"]),
+        ("api_key" = ["admin"])
+    )
+)]
+pub async fn unlock_user(
+  State(state): State<AppState>,
+  admin_id: UserId,
+  Path(username): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+  let Some(mut user) = state.db_repo.find_user_by_username(&username).await? else {
+      return Err(AppError::NotFound("User not found".to_string()));
+  };
+  state
+      .db_repo
+      .reset_failed_logins(user._id.expect("User from DB should have an ID"))
+      .await?;
+  warn!("SECURITY: account '{}' unlocked by admin {}", username, admin_id.0);
+  user.failed_login_count = 0;
+  user.last_failed_login_at = None;
+  user.locked_until = None;
+  Ok((StatusCode::OK, Json(UserResponse::from_user(&user))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "user",
+    responses(
+        (status = 200, description = "Public keys that verify access tokens, matched by the token's `kid`", body = JwkSet)
+    ),
+)]
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+  // Short enough that a newly added verification key is picked up well
+  // before tokens signed with it are common.
+  (
+      [(header::CACHE_CONTROL, "public, max-age=300")],
+      Json(state.jwt_keys.jwks().clone()),
+  )
+}
@@ -0,0 +1,319 @@
+use std::{fs, str::FromStr};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use ed25519_dalek::{
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    SigningKey as Ed25519SigningKey, VerifyingKey as Ed25519VerifyingKey,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey},
+    traits::PublicKeyParts,
+    RsaPrivateKey, RsaPublicKey,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::config::Config;
+
+#[derive(Debug, Error)]
+pub enum JwtKeyError {
+    #[error("JWT_SECRET must be set when JWT_ALGORITHM is HS256")]
+    MissingSecret,
+    #[error("JWT_PRIVATE_KEY_PATH must be set when JWT_ALGORITHM is {0}")]
+    MissingPrivateKey(&'static str),
+    #[error("Failed to read key file {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("{path} is not a PEM encoded {expected} key")]
+    InvalidKey { path: String, expected: &'static str },
+    #[error("Invalid key material: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+}
+
+/// Signing algorithms the service can issue tokens with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    Hs256,
+    Rs256,
+    EdDsa,
+}
+
+impl JwtAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JwtAlgorithm::Hs256 => "HS256",
+            JwtAlgorithm::Rs256 => "RS256",
+            JwtAlgorithm::EdDsa => "EdDSA",
+        }
+    }
+}
+
+impl FromStr for JwtAlgorithm {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_uppercase().as_str() {
+            "HS256" => Ok(JwtAlgorithm::Hs256),
+            "RS256" => Ok(JwtAlgorithm::Rs256),
+            "EDDSA" => Ok(JwtAlgorithm::EdDsa),
+            other => Err(format!("unsupported JWT algorithm '{}'", other)),
+        }
+    }
+}
+
+/// Public key in JWK form (RFC 7517), as served from `/.well-known/jwks.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Jwk {
+    /// `RSA` or `OKP`
+    pub kty: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    pub alg: String,
+    pub kid: String,
+    /// RSA modulus, base64url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// RSA public exponent, base64url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    /// Curve of an `OKP` key, always `Ed25519`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    /// Ed25519 public key, base64url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    decoding: DecodingKey,
+}
+
+/// Key material for issuing and checking access tokens.
+///
+/// Tokens are signed with a single active key and carry its `kid`. Any key in
+/// `verifiers` is accepted, so a new key can be rolled out while tokens signed
+/// by the previous one are still live. Asymmetric key ids are RFC 7638
+/// thumbprints, which keeps them stable across restarts and replicas.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    kid: Option<String>,
+    encoding: EncodingKey,
+    verifiers: Vec<VerificationKey>,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    pub fn from_config(config: &Config) -> Result<Self, JwtKeyError> {
+        let mut keys = match config.jwt_algorithm {
+            JwtAlgorithm::Hs256 => Self::hmac(&config.jwt_secret)?,
+            algorithm => {
+                let path = config
+                    .jwt_private_key_path
+                    .as_deref()
+                    .ok_or(JwtKeyError::MissingPrivateKey(algorithm.as_str()))?;
+                Self::asymmetric(algorithm, path)?
+            }
+        };
+        for path in &config.jwt_verification_key_paths {
+            let (jwk, decoding) = load_public_key(path)?;
+            keys.add_verifier(jwk, decoding);
+        }
+        info!(
+            "JWT signing with {:?} (kid {}), {} verification key(s)",
+            keys.algorithm,
+            keys.kid.as_deref().unwrap_or("none"),
+            keys.verifiers.len()
+        );
+        Ok(keys)
+    }
+
+    /// Symmetric keys are never published, and tokens signed with them carry
+    /// no `kid`: there is nothing for other services to look up.
+    fn hmac(secret: &str) -> Result<Self, JwtKeyError> {
+        if secret.is_empty() {
+            return Err(JwtKeyError::MissingSecret);
+        }
+        Ok(Self {
+            algorithm: Algorithm::HS256,
+            kid: None,
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            verifiers: vec![VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                decoding: DecodingKey::from_secret(secret.as_bytes()),
+            }],
+            jwks: JwkSet::default(),
+        })
+    }
+
+    fn asymmetric(algorithm: JwtAlgorithm, path: &str) -> Result<Self, JwtKeyError> {
+        let pem = read_key_file(path)?;
+        let (encoding, jwk) = match algorithm {
+            JwtAlgorithm::Rs256 => {
+                let private = RsaPrivateKey::from_pkcs8_pem(&pem)
+                    .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))
+                    .map_err(|_| invalid_key(path, "RSA private"))?;
+                (
+                    EncodingKey::from_rsa_pem(pem.as_bytes())?,
+                    rsa_jwk(&RsaPublicKey::from(&private)),
+                )
+            }
+            JwtAlgorithm::EdDsa => {
+                let private = Ed25519SigningKey::from_pkcs8_pem(&pem)
+                    .map_err(|_| invalid_key(path, "Ed25519 private"))?;
+                (
+                    EncodingKey::from_ed_pem(pem.as_bytes())?,
+                    ed25519_jwk(&private.verifying_key()),
+                )
+            }
+            JwtAlgorithm::Hs256 => unreachable!("HS256 keys are built by JwtKeys::hmac"),
+        };
+        let decoding = decoding_key(&jwk)?;
+
+        let mut keys = Self {
+            algorithm: algorithm_of(&jwk),
+            kid: Some(jwk.kid.clone()),
+            encoding,
+            verifiers: Vec::new(),
+            jwks: JwkSet::default(),
+        };
+        keys.add_verifier(jwk, decoding);
+        Ok(keys)
+    }
+
+    fn add_verifier(&mut self, jwk: Jwk, decoding: DecodingKey) {
+        if self.jwks.keys.iter().any(|known| known.kid == jwk.kid) {
+            return;
+        }
+        self.verifiers.push(VerificationKey {
+            kid: Some(jwk.kid.clone()),
+            algorithm: algorithm_of(&jwk),
+            decoding,
+        });
+        self.jwks.keys.push(jwk);
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding
+    }
+
+    /// Key for a token's `kid`. Tokens without one predate key ids and can
+    /// only have been signed by the active key.
+    pub fn decoding_key(&self, kid: Option<&str>) -> Option<(Algorithm, &DecodingKey)> {
+        let verifier = match kid {
+            Some(kid) => self.verifiers.iter().find(|v| v.kid.as_deref() == Some(kid)),
+            None => self.verifiers.iter().find(|v| v.kid == self.kid),
+        }?;
+        Some((verifier.algorithm, &verifier.decoding))
+    }
+
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+fn read_key_file(path: &str) -> Result<String, JwtKeyError> {
+    fs::read_to_string(path).map_err(|source| JwtKeyError::Read {
+        path: path.to_string(),
+        source,
+    })
+}
+
+fn invalid_key(path: &str, expected: &'static str) -> JwtKeyError {
+    JwtKeyError::InvalidKey {
+        path: path.to_string(),
+        expected,
+    }
+}
+
+/// Loads a retired or upcoming public key. The algorithm follows the key type,
+/// so rotating from RSA to Ed25519 (or back) needs no extra configuration.
+fn load_public_key(path: &str) -> Result<(Jwk, DecodingKey), JwtKeyError> {
+    let pem = read_key_file(path)?;
+    let jwk = if let Ok(public) = RsaPublicKey::from_public_key_pem(&pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(&pem))
+    {
+        rsa_jwk(&public)
+    } else if let Ok(public) = Ed25519VerifyingKey::from_public_key_pem(&pem) {
+        ed25519_jwk(&public)
+    } else {
+        return Err(invalid_key(path, "RSA or Ed25519 public"));
+    };
+    let decoding = decoding_key(&jwk)?;
+    Ok((jwk, decoding))
+}
+
+fn rsa_jwk(public: &RsaPublicKey) -> Jwk {
+    let n = URL_SAFE_NO_PAD.encode(public.n().to_bytes_be());
+    let e = URL_SAFE_NO_PAD.encode(public.e().to_bytes_be());
+    let kid = thumbprint(&format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n));
+    Jwk {
+        kty: "RSA".to_string(),
+        key_use: "sig".to_string(),
+        alg: JwtAlgorithm::Rs256.as_str().to_string(),
+        kid,
+        n: Some(n),
+        e: Some(e),
+        crv: None,
+        x: None,
+    }
+}
+
+fn ed25519_jwk(public: &Ed25519VerifyingKey) -> Jwk {
+    let x = URL_SAFE_NO_PAD.encode(public.as_bytes());
+    let kid = thumbprint(&format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x));
+    Jwk {
+        kty: "OKP".to_string(),
+        key_use: "sig".to_string(),
+        alg: JwtAlgorithm::EdDsa.as_str().to_string(),
+        kid,
+        n: None,
+        e: None,
+        crv: Some("Ed25519".to_string()),
+        x: Some(x),
+    }
+}
+
+/// RFC 7638 thumbprint over the key's required members in lexicographic order.
+fn thumbprint(canonical: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+fn algorithm_of(jwk: &Jwk) -> Algorithm {
+    if jwk.kty == "RSA" {
+        Algorithm::RS256
+    } else {
+        Algorithm::EdDSA
+    }
+}
+
+/// Verification keys are built from the published JWK, so what other
+/// services see in the JWKS is exactly what this service checks against.
+fn decoding_key(jwk: &Jwk) -> Result<DecodingKey, JwtKeyError> {
+    match (&jwk.n, &jwk.e, &jwk.x) {
+        (Some(n), Some(e), _) => Ok(DecodingKey::from_rsa_components(n, e)?),
+        (_, _, Some(x)) => Ok(DecodingKey::from_ed_components(x)?),
+        _ => unreachable!("JWKs are only built for RSA and Ed25519 keys"),
+    }
+}
@@ -1,21 +1,29 @@
 use crate::{
-    auth::utils::{JWTError, validate_jwt},
+    auth::utils::{hash_token, validate_jwt},
+    error::AppError,
     state::AppState,
 };
 use axum::{
     extract::{FromRef, FromRequestParts, Request, State},
-    http::{HeaderMap, StatusCode, request::Parts},
+    http::{HeaderMap, request::Parts},
     middleware::Next,
     response::Response,
 };
+use chrono::{Duration, Utc};
+use mongodb::bson::oid::ObjectId;
 use tracing::warn;
 
-use super::models::UserId;
+use super::models::{AuthMethod, Grants, Role, Scope, UserId};
 
 const AUTH_HEADER_NAME: &str = "Authorization";
 const AUTH_SCHEME: &str = "Bearer ";
+pub(crate) const API_KEY_HEADER_NAME: &str = "X-API-Key";
 
-fn extract_token(headers: &HeaderMap) -> Option<String> {
+/// `last_used_at` is only rewritten once it is this far behind, so a busy key
+/// does not cost a database write per request.
+const API_KEY_TOUCH_INTERVAL_SECS: i64 = 60;
+
+pub(crate) fn extract_token(headers: &HeaderMap) -> Option<String> {
     headers
         .get(AUTH_HEADER_NAME)
         .and_then(|header| header.to_str().ok())
@@ -23,40 +31,140 @@ fn extract_token(headers: &HeaderMap) -> Option<String> {
         .map(|token| token.to_string())
 }
 
+/// Accepts either a `Bearer` access token or an `X-API-Key` personal API key.
 pub async fn auth_middleware(
     State(state): State<AppState>,
     mut req: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, AppError> {
     let headers = req.headers();
+    if let Some(api_key) = headers.get(API_KEY_HEADER_NAME) {
+        let api_key = api_key.to_str().unwrap_or_default().to_string();
+        let (user_id, role, grants) = authenticate_api_key(&state, &api_key).await?;
+        req.extensions_mut().insert(user_id);
+        req.extensions_mut().insert(role);
+        req.extensions_mut().insert(grants);
+        req.extensions_mut().insert(AuthMethod::ApiKey);
+        return Ok(next.run(req).await);
+    }
+
     let token = extract_token(headers).ok_or_else(|| {
         warn!("Authentication failed: Missing or malformed Authorization header");
-        StatusCode::UNAUTHORIZED
+        AppError::Unauthorized("Missing or malformed Authorization header".to_string())
     })?;
 
-    let claims = validate_jwt(&token, &state.config).map_err(|e| match e {
-        JWTError::Expired => StatusCode::UNAUTHORIZED,
-        JWTError::ValidationFailed(_) | JWTError::InvalidFormat => StatusCode::UNAUTHORIZED,
-        JWTError::CreationFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
-    })?;
+    let claims = validate_jwt(&token, &state.jwt_keys, state.db_repo.as_ref())
+        .await
+        .inspect_err(|e| {
+            warn!("Authentication failed: {}", e);
+        })?;
 
     let user_id = UserId(claims.sub);
     req.extensions_mut().insert(user_id);
+    req.extensions_mut().insert(claims.role);
+    req.extensions_mut().insert(Grants(claims.role.scopes().to_vec()));
+    req.extensions_mut().insert(AuthMethod::Session);
+
+    Ok(next.run(req).await)
+}
+
+/// A key acts for its owner with its own scopes, narrowed to whatever the
+/// owner's role grants today, so demoting a user also demotes their keys.
+async fn authenticate_api_key(
+    state: &AppState,
+    api_key: &str,
+) -> Result<(UserId, Role, Grants), AppError> {
+    let invalid = || AppError::Unauthorized("Invalid API key".to_string());
+    let now = Utc::now();
+
+    let key = state
+        .db_repo
+        .find_api_key_by_hash(&hash_token(api_key))
+        .await?
+        .ok_or_else(|| {
+            warn!("Authentication failed: unknown API key");
+            invalid()
+        })?;
+    let key_id = key._id.expect("API key from DB should have an ID");
+    if !key.is_active(now) {
+        warn!("Authentication failed: API key {} is revoked or expired", key_id);
+        return Err(invalid());
+    }
+    let owner = state
+        .db_repo
+        .find_user_by_id(key.user_id)
+        .await?
+        .ok_or_else(|| {
+            warn!("Authentication failed: owner of API key {} no longer exists", key_id);
+            invalid()
+        })?;
+
+    let grants = key
+        .scopes
+        .iter()
+        .copied()
+        .filter(|scope| owner.role.grants(*scope))
+        .collect();
+    state
+        .db_repo
+        .touch_api_key(key_id, now, now - Duration::seconds(API_KEY_TOUCH_INTERVAL_SECS))
+        .await?;
 
+    Ok((
+        UserId(key.user_id.to_hex()),
+        owner.role,
+        Grants(grants),
+    ))
+}
+
+/// Per-route authorization, layered inside `auth_middleware`:
+/// `route_layer(middleware::from_fn_with_state(Scope::ProductsWrite, require_scope))`.
+pub async fn require_scope(
+    State(scope): State<Scope>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let allowed = req
+        .extensions()
+        .get::<Grants>()
+        .is_some_and(|grants| grants.allows(scope));
+    if !allowed {
+        let role = req.extensions().get::<Role>().copied().unwrap_or_default();
+        warn!(
+            "Authorization failed: scope '{}' not granted (role '{}') for {} {}",
+            scope.as_str(),
+            role.as_str(),
+            req.method(),
+            req.uri().path()
+        );
+        return Err(AppError::Forbidden(format!(
+            "This action requires the '{}' scope",
+            scope.as_str()
+        )));
+    }
     Ok(next.run(req).await)
 }
 
+impl UserId {
+    pub fn object_id(&self) -> Result<ObjectId, AppError> {
+        self.0
+            .parse()
+            .map_err(|_| AppError::Unauthorized("Invalid user in token".to_string()))
+    }
+}
+
 impl<S> FromRequestParts<S> for UserId
 where
     AppState: FromRef<S>,
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        parts.extensions.get::<UserId>().cloned().ok_or((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "User ID not found in request extensions.",
-        ))
+        parts
+            .extensions
+            .get::<UserId>()
+            .cloned()
+            .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string()))
     }
 }
@@ -1,4 +1,7 @@
+pub mod bootstrap;
+pub mod keys;
 pub mod models;
+pub mod throttle;
 pub mod utils;
 pub mod handlers;
 pub mod middleware;
\ No newline at end of file
@@ -1,6 +1,10 @@
+use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use validator::Validate;
+
+use super::utils::{validate_password_strength, validate_username};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
@@ -8,11 +12,231 @@ pub struct User {
     pub _id: Option<ObjectId>,
     pub username: String,
     pub password_hash: String,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Whether the owner proved they read `email`; reset on every change
+    #[serde(default)]
+    pub email_verified: bool,
+    /// Failed logins since `last_failed_login_at`'s window started
+    #[serde(default)]
+    pub failed_login_count: u32,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub last_failed_login_at: Option<DateTime<Utc>>,
+    /// Logins are refused until then, even with the right password
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub locked_until: Option<DateTime<Utc>>,
+    /// Access tokens issued at an older epoch are refused
+    #[serde(default)]
+    pub token_epoch: u32,
 }
 
-#[derive(Deserialize, Debug, ToSchema)]
+impl User {
+    pub fn new(username: String, password_hash: String, role: Role) -> Self {
+        Self {
+            _id: Some(ObjectId::new()),
+            username,
+            password_hash,
+            role,
+            display_name: None,
+            email: None,
+            email_verified: false,
+            failed_login_count: 0,
+            last_failed_login_at: None,
+            locked_until: None,
+            token_epoch: 0,
+        }
+    }
+}
+
+/// What a user may do. Each role includes everything the roles below it can.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Editor,
+    #[default]
+    Viewer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn scopes(&self) -> &'static [Scope] {
+        match self {
+            Role::Admin => &[
+                Scope::ProductsRead,
+                Scope::ProductsWrite,
+                Scope::MessagesRead,
+                Scope::Admin,
+            ],
+            Role::Editor => &[Scope::ProductsRead, Scope::ProductsWrite, Scope::MessagesRead],
+            Role::Viewer => &[Scope::ProductsRead, Scope::MessagesRead],
+        }
+    }
+
+    pub fn grants(&self, scope: Scope) -> bool {
+        self.scopes().contains(&scope)
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "admin" => Ok(Role::Admin),
+            "editor" => Ok(Role::Editor),
+            "viewer" => Ok(Role::Viewer),
+            other => Err(format!("unknown role `{}`", other)),
+        }
+    }
+}
+
+/// Permission checked per route. The names are the OAuth-style scopes listed
+/// under each operation's `security` requirement in the OpenAPI spec.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum Scope {
+    #[serde(rename = "products:read")]
+    ProductsRead,
+    #[serde(rename = "products:write")]
+    ProductsWrite,
+    #[serde(rename = "messages:read")]
+    MessagesRead,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ProductsRead => "products:read",
+            Scope::ProductsWrite => "products:write",
+            Scope::MessagesRead => "messages:read",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// Scopes the current request may use, set by `auth_middleware`: the role's
+/// scopes for a session, or an API key's scopes capped by its owner's role.
+#[derive(Debug, Clone)]
+pub struct Grants(pub Vec<Scope>);
+
+impl Grants {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.0.contains(&scope)
+    }
+}
+
+/// How the current request authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Session,
+    ApiKey,
+}
+
+/// Personal API key. The key itself is shown once at creation; only its
+/// SHA-256 hash and a short prefix, for telling keys apart, are stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKey {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<Scope>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+#[derive(Deserialize, Debug, ToSchema, Validate)]
+pub struct CreateApiKeyRequest {
+    /// What the key is for, e.g. `ci-deploy`
+    #[schema(min_length = 1, max_length = 64)]
+    #[validate(length(min = 1, max = 64, message = "must be between 1 and 64 characters"))]
+    pub name: String,
+    /// Defaults to every scope of your role; may not exceed it
+    pub scopes: Option<Vec<Scope>>,
+    /// Lifetime in days (default 90)
+    #[schema(minimum = 1, maximum = 365)]
+    #[validate(range(min = 1, max = 365, message = "must be between 1 and 365 days"))]
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    /// First characters of the key, for recognising it
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: String,
+    pub expires_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked: bool,
+}
+
+impl ApiKeyResponse {
+    pub fn from_api_key(key: &ApiKey) -> Self {
+        ApiKeyResponse {
+            id: key._id.expect("API key from DB should have an ID").to_hex(),
+            name: key.name.clone(),
+            prefix: key.prefix.clone(),
+            scopes: key.scopes.clone(),
+            created_at: key.created_at.to_string(),
+            expires_at: key.expires_at.to_string(),
+            last_used_at: key.last_used_at.map(|at| at.to_string()),
+            revoked: key.revoked_at.is_some(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct CreatedApiKeyResponse {
+    /// The API key. Send it as `X-API-Key`; it cannot be shown again
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Deserialize, Debug, ToSchema, Validate)]
 pub struct SignupRequest {
+    /// Letters, digits, `_`, `-` and `.`
+    #[schema(min_length = 3, max_length = 32, pattern = r"^[A-Za-z0-9_.-]+$")]
+    #[validate(
+        length(min = 3, max = 32, message = "must be between 3 and 32 characters"),
+        custom(function = "validate_username")
+    )]
     pub username: String,
+    /// At least one letter and one digit
+    #[schema(min_length = 8, max_length = 128)]
+    #[validate(
+        length(min = 8, max = 128, message = "must be between 8 and 128 characters"),
+        custom(function = "validate_password_strength")
+    )]
     pub password: String,
 }
 
@@ -24,14 +248,184 @@ pub struct LoginRequest {
 
 #[derive(Serialize, Debug, ToSchema)]
 pub struct AuthResponse {
+    /// Short-lived access token for the `Authorization` header
     pub token: String,
     pub token_type: String,
+    /// Seconds until `token` expires
+    pub expires_in: u64,
+    /// Single-use token for `/auth/refresh`; each use returns a new one
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Server-side record of an issued refresh token. Only the SHA-256 hash of
+/// the token is stored. Every token minted by rotating another shares its
+/// `family_id`, so a replayed token can take the whole chain down.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefreshToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub family_id: String,
+    pub token_hash: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub used_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Denylisted access token, kept until the token would have expired anyway.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevokedToken {
+    #[serde(rename = "_id")]
+    pub jti: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::PasswordReset => "password_reset",
+            TokenPurpose::EmailVerification => "email_verification",
+        }
+    }
+}
+
+/// Single-use token mailed to a user, stored by SHA-256 hash like refresh
+/// tokens. Verification tokens remember the address they were sent to, so a
+/// link for an old address cannot verify a newer one.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub purpose: TokenPurpose,
+    pub token_hash: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub used_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    /// Unique token id, checked against the revocation denylist
+    #[serde(default)]
+    pub jti: String,
+    /// Tokens issued before roles existed carry none and act as viewers.
+    #[serde(default)]
+    pub role: Role,
+    /// The user's `token_epoch` when the token was issued
+    #[serde(default)]
+    pub epoch: u32,
+}
+
+/// Fields left out are kept as they are.
+#[derive(Deserialize, Debug, ToSchema, Validate)]
+pub struct UpdateProfileRequest {
+    #[schema(min_length = 1, max_length = 64)]
+    #[validate(length(min = 1, max = 64, message = "must be between 1 and 64 characters"))]
+    pub display_name: Option<String>,
+    #[schema(format = "email", max_length = 254)]
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 254, message = "must be at most 254 characters")
+    )]
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema, Validate)]
+pub struct ForgotPasswordRequest {
+    /// Verified email address of the account
+    #[schema(format = "email")]
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema, Validate)]
+pub struct ResetPasswordRequest {
+    /// Token from the reset email
+    pub token: String,
+    /// At least one letter and one digit
+    #[schema(min_length = 8, max_length = 128)]
+    #[validate(
+        length(min = 8, max = 128, message = "must be between 8 and 128 characters"),
+        custom(function = "validate_password_strength")
+    )]
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct VerifyEmailRequest {
+    /// Token from the verification email
+    pub token: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema, Validate)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    /// At least one letter and one digit
+    #[schema(min_length = 8, max_length = 128)]
+    #[validate(
+        length(min = 8, max = 128, message = "must be between 8 and 128 characters"),
+        custom(function = "validate_password_strength")
+    )]
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct UpdateRoleRequest {
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct UserResponse {
+    pub id: String,
+    pub username: String,
+    pub role: Role,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    /// Set while the account is locked out after repeated failed logins
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl UserResponse {
+    pub fn from_user(user: &User) -> Self {
+        UserResponse {
+            id: user._id.expect("User from DB should have an ID").to_hex(),
+            username: user.username.clone(),
+            role: user.role,
+            display_name: user.display_name.clone(),
+            email: user.email.clone(),
+            email_verified: user.email_verified,
+            locked_until: user.locked_until.filter(|until| *until > Utc::now()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
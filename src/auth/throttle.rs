@@ -0,0 +1,236 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use chrono::{DateTime, Utc};
+
+use crate::{auth::models::User, config::Config, state::AppState};
+
+/// Longest a single login attempt is held back, however many failures led up
+/// to it.
+const MAX_LOGIN_DELAY: Duration = Duration::from_secs(5);
+
+/// Failed-login limits, from `LOGIN_*` settings.
+#[derive(Debug, Clone)]
+pub struct LoginPolicy {
+    pub max_failures: u32,
+    pub ip_max_failures: u32,
+    pub failure_window: Duration,
+    pub lockout: Duration,
+    pub delay_base: Duration,
+}
+
+impl LoginPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_failures: config.login_max_failures,
+            ip_max_failures: config.login_ip_max_failures,
+            failure_window: Duration::from_secs(config.login_failure_window_secs),
+            lockout: Duration::from_secs(config.login_lockout_secs),
+            delay_base: Duration::from_millis(config.login_delay_base_ms),
+        }
+    }
+
+    /// Doubles with every recent failure: 0, base, 2×base, 4×base, …
+    pub fn delay(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32 << (failures - 1).min(16);
+        self.delay_base.saturating_mul(factor).min(MAX_LOGIN_DELAY)
+    }
+
+    /// Start of the window in which failures still count.
+    pub fn window_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - chrono::Duration::from_std(self.failure_window).unwrap_or_default()
+    }
+
+    /// Failures of `user` that still count against it at `now`.
+    pub fn recent_failures(&self, user: &User, now: DateTime<Utc>) -> u32 {
+        match user.last_failed_login_at {
+            Some(at) if at >= self.window_start(now) => user.failed_login_count,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct IpFailures {
+    count: u32,
+    window_started: Instant,
+}
+
+/// The per-user counters of a username that has no account.
+#[derive(Debug)]
+struct UnknownUserFailures {
+    count: u32,
+    last_failed_at: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+/// Failed logins per client address, and per username that has no account.
+/// Unlike the per-user counters these live in process memory, so each
+/// instance tracks what it has seen. The address counts slow down sweeps
+/// across many usernames, which per-user lockout cannot catch; the username
+/// counts make a missing account fail and lock like an existing one, so the
+/// lockout does not reveal which usernames exist.
+pub struct LoginThrottle {
+    policy: LoginPolicy,
+    by_ip: Mutex<HashMap<IpAddr, IpFailures>>,
+    by_unknown_user: Mutex<HashMap<String, UnknownUserFailures>>,
+}
+
+impl LoginThrottle {
+    pub fn new(policy: LoginPolicy) -> Self {
+        Self {
+            policy,
+            by_ip: Mutex::new(HashMap::new()),
+            by_unknown_user: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn policy(&self) -> &LoginPolicy {
+        &self.policy
+    }
+
+    /// Failures from `ip` within the current window.
+    pub fn ip_failures(&self, ip: IpAddr) -> u32 {
+        let by_ip = self.by_ip.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        by_ip
+            .get(&ip)
+            .filter(|entry| entry.window_started.elapsed() < self.policy.failure_window)
+            .map_or(0, |entry| entry.count)
+    }
+
+    /// Seconds until `ip` may try again, if it has used up its failures.
+    pub fn ip_blocked_for(&self, ip: IpAddr) -> Option<u64> {
+        let by_ip = self.by_ip.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entry = by_ip.get(&ip)?;
+        let remaining = self
+            .policy
+            .failure_window
+            .checked_sub(entry.window_started.elapsed())?;
+        (entry.count >= self.policy.ip_max_failures).then(|| remaining.as_secs().max(1))
+    }
+
+    /// Counts a failure from `ip`, returning the failures in its window.
+    pub fn record_ip_failure(&self, ip: IpAddr) -> u32 {
+        let mut by_ip = self.by_ip.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let window = self.policy.failure_window;
+        // Drop stale entries here rather than on a timer; failures are rare
+        // enough that the sweep is cheap.
+        by_ip.retain(|_, entry| entry.window_started.elapsed() < window);
+        let entry = by_ip.entry(ip).or_insert_with(|| IpFailures {
+            count: 0,
+            window_started: Instant::now(),
+        });
+        entry.count += 1;
+        entry.count
+    }
+
+    /// When the lockout of a username without an account ends, if it is
+    /// locked at `now`.
+    pub fn unknown_user_locked_until(
+        &self,
+        username: &str,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let by_user = self
+            .by_unknown_user
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        by_user
+            .get(username)
+            .and_then(|entry| entry.locked_until)
+            .filter(|until| *until > now)
+    }
+
+    /// Failures of a username without an account that still count at `now`.
+    pub fn unknown_user_failures(&self, username: &str, now: DateTime<Utc>) -> u32 {
+        let by_user = self
+            .by_unknown_user
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        by_user
+            .get(username)
+            .filter(|entry| entry.last_failed_at >= self.policy.window_start(now))
+            .map_or(0, |entry| entry.count)
+    }
+
+    /// Counts a failure for a username without an account, locking it the
+    /// way `login` locks an account. Returns the failures in its window.
+    pub fn record_unknown_user_failure(&self, username: &str, now: DateTime<Utc>) -> u32 {
+        let mut by_user = self
+            .by_unknown_user
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let window_start = self.policy.window_start(now);
+        by_user.retain(|_, entry| {
+            entry.last_failed_at >= window_start
+                || entry.locked_until.is_some_and(|until| until > now)
+        });
+        let entry = by_user
+            .entry(username.to_string())
+            .or_insert_with(|| UnknownUserFailures {
+                count: 0,
+                last_failed_at: now,
+                locked_until: None,
+            });
+        if entry.last_failed_at < window_start {
+            entry.count = 0;
+        }
+        entry.count += 1;
+        entry.last_failed_at = now;
+        if entry.count >= self.policy.max_failures {
+            let lockout = chrono::Duration::from_std(self.policy.lockout).unwrap_or_default();
+            entry.locked_until = Some(now + lockout);
+        }
+        entry.count
+    }
+}
+
+/// Address of the client making the request. `X-Forwarded-For` is only
+/// believed when `TRUST_FORWARDED_FOR` is set, i.e. behind a proxy that
+/// overwrites it; otherwise any client could pick its own address.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_ref(state);
+        let forwarded = state
+            .config
+            .trust_forwarded_for
+            .then(|| {
+                parts
+                    .headers
+                    .get("x-forwarded-for")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.split(',').next())
+                    .and_then(|first| first.trim().parse().ok())
+            })
+            .flatten();
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(ClientIp(
+            forwarded
+                .or(peer)
+                .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        ))
+    }
+}
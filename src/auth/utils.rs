@@ -1,11 +1,20 @@
-use crate::auth::models::Claims;
+use crate::auth::models::{Claims, Role};
+use crate::auth::keys::JwtKeys;
 use crate::config::Config;
+use crate::db::repository::Repository;
+use crate::error::AppError;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use bcrypt::{hash, verify, BcryptError, DEFAULT_COST};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{
-    decode, encode, errors::Error as JwtErrorInternal, DecodingKey, EncodingKey, Header, Validation,
+    decode, decode_header, encode, errors::Error as JwtErrorInternal, Header, Validation,
 };
+use mongodb::bson::oid::ObjectId;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use uuid::Uuid;
+use validator::ValidationError;
 
 #[derive(Debug, Error)]
 pub enum JWTError {
@@ -17,8 +26,12 @@ pub enum JWTError {
     Expired,
     #[error("Invalid JWT format")]
     InvalidFormat,
+    #[error("JWT has been revoked")]
+    Revoked,
 }
 
+const OPAQUE_TOKEN_BYTES: usize = 32;
+
 pub fn hash_password(password: &str) -> Result<String, BcryptError> {
     hash(password, DEFAULT_COST)
 }
@@ -27,31 +40,100 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, BcryptError>
     verify(password, hash)
 }
 
-pub fn create_jwt(user_id: &str, config: &Config) -> Result<String, JWTError> {
+pub fn create_jwt(
+    user_id: &str,
+    role: Role,
+    epoch: u32,
+    config: &Config,
+    keys: &JwtKeys,
+) -> Result<String, JWTError> {
     let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(config.jwt_expiration_hours as i64))
+        .checked_add_signed(Duration::minutes(config.access_token_ttl_minutes as i64))
         .expect("valid timestamp")
         .timestamp();
 
     let claims = Claims {
         sub: user_id.to_owned(),
         exp: expiration as usize,
+        jti: Uuid::new_v4().to_string(),
+        role,
+        epoch,
     };
 
-    let header = Header::default();
-    let key = EncodingKey::from_secret(config.jwt_secret.as_ref());
+    let mut header = Header::new(keys.algorithm());
+    header.kid = keys.kid().map(str::to_string);
 
-    encode(&header, &claims, &key).map_err(JWTError::CreationFailed)
+    encode(&header, &claims, keys.encoding_key()).map_err(JWTError::CreationFailed)
 }
 
-pub fn validate_jwt(token: &str, config: &Config) -> Result<Claims, JWTError> {
-    let key = DecodingKey::from_secret(config.jwt_secret.as_ref());
-    let validation = Validation::default();
+/// Checks signature and expiry only. Use `validate_jwt` to authenticate
+/// requests, which also honours the revocation denylist.
+pub fn decode_jwt(token: &str, keys: &JwtKeys) -> Result<Claims, JWTError> {
+    let header = decode_header(token).map_err(|_| JWTError::InvalidFormat)?;
+    // The algorithm comes from our key, never from the token, so a token
+    // cannot pick a weaker check than the one its key was issued for.
+    let (algorithm, key) = keys
+        .decoding_key(header.kid.as_deref())
+        .ok_or_else(|| JWTError::ValidationFailed("unknown signing key".to_string()))?;
+    let validation = Validation::new(algorithm);
 
-    decode::<Claims>(token, &key, &validation).map(|data| data.claims)
+    decode::<Claims>(token, key, &validation).map(|data| data.claims)
      .map_err(|err| match err.kind() {
          jsonwebtoken::errors::ErrorKind::ExpiredSignature => JWTError::Expired,
          jsonwebtoken::errors::ErrorKind::InvalidToken => JWTError::InvalidFormat,
          _ => JWTError::ValidationFailed(err.to_string()),
      })
-}
\ No newline at end of file
+}
+
+pub async fn validate_jwt(
+    token: &str,
+    keys: &JwtKeys,
+    repo: &dyn Repository,
+) -> Result<Claims, AppError> {
+    let claims = decode_jwt(token, keys)?;
+    if !claims.jti.is_empty() && repo.is_access_token_revoked(&claims.jti).await? {
+        return Err(JWTError::Revoked.into());
+    }
+    // Tokens die with their account, and a password change or reset moves
+    // the user to a new epoch, retiring every token issued before it.
+    let user_id = ObjectId::parse_str(&claims.sub).map_err(|_| JWTError::InvalidFormat)?;
+    let user = repo.find_user_by_id(user_id).await?;
+    if user.is_none_or(|user| claims.epoch < user.token_epoch) {
+        return Err(JWTError::Revoked.into());
+    }
+    Ok(claims)
+}
+
+/// Opaque, URL-safe token for refresh, reset and verification links. Only its
+/// hash is ever stored.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; OPAQUE_TOKEN_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn validate_username(username: &str) -> Result<(), ValidationError> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    if !username.chars().all(allowed) {
+        return Err(ValidationError::new("charset")
+            .with_message("may only contain letters, digits, '_', '-' and '.'".into()));
+    }
+    Ok(())
+}
+
+pub fn validate_password_strength(password: &str) -> Result<(), ValidationError> {
+    let has_letter = password.chars().any(char::is_alphabetic);
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+    if !has_letter || !has_digit {
+        return Err(ValidationError::new("strength")
+            .with_message("must contain at least one letter and one digit".into()));
+    }
+    Ok(())
+}
@@ -1,48 +1,31 @@
-use bson::oid::ObjectId;
-use rdkafka::config::ClientConfig;
-use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::message::Message;
+use std::sync::Arc;
+
 use rs_kafka_mongo::config::Config;
 use rs_kafka_mongo::db::mongo::MongoRepo;
-use rs_kafka_mongo::message::models::Message as EventMessage;
-use tokio_stream::StreamExt;
+use rs_kafka_mongo::kafka::consumer::EventConsumer;
+use rs_kafka_mongo::kafka::producer::AppKafkaProducer;
+use rs_kafka_mongo::message::recorder::MessageRecorder;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::from_env()?;
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "rs_kafka_mongo=info".into()),
+        ))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
 
-    let db_repo = MongoRepo::init(&config.database_url, &config.database_name).await?;
-
-    let consumer: StreamConsumer = ClientConfig::new()
-        .set("group.id", "product-event-listener")
-        .set("bootstrap.servers", "kafka:29092")
-        .set("auto.offset.reset", "earliest")
-        .create()
-        .expect("Consumer creation failed");
+    let config = Config::from_env()?;
 
-    consumer
-        .subscribe(&["product_events"])
-        .expect("Can't subscribe to specified topic");
+    let db_repo = Arc::new(MongoRepo::init(&config.database_url, &config.database_name).await?);
 
-    println!("Listening to Kafka topic 'product-events'...");
+    let publisher = Arc::new(AppKafkaProducer::new(&config.kafka_brokers)?);
 
-    let mut message_stream = consumer.stream();
-    
-    while let Some(message_result) = message_stream.next().await {
-        match message_result {
-            Ok(message) => {
-                if let Ok(payload) = message.payload_view::<str>().unwrap() {
-                    println!("Received: {}", payload);
-                    let new_message = EventMessage {
-                        _id: Some(ObjectId::new()),
-                        message: payload.to_string()
-                    };
-                    db_repo.create_message(new_message).await?;
-                }
-            }
-            Err(e) => eprintln!("Error while reading from stream: {}", e),
-        }
-    }
+    EventConsumer::from_config(&config, publisher, db_repo.clone())?
+        .register(MessageRecorder::new(db_repo))
+        .run()
+        .await?;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,32 +1,199 @@
 use dotenvy::dotenv;
 use std::env;
 
+use crate::auth::{keys::JwtAlgorithm, models::Role};
+use crate::mail::mailer::MailerKind;
+use crate::products::money::Currency;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub server_addr: String,
     pub database_url: String,
     pub database_name: String,
+    pub database_auto_migrate: bool,
     pub kafka_brokers: String,
+    pub kafka_enabled: bool,
     pub kafka_product_events_topic: String,
+    pub kafka_consumer_group: String,
+    pub kafka_consumer_max_retries: u32,
+    pub kafka_consumer_retry_backoff_ms: u64,
+    pub kafka_dead_letter_topic: String,
+    pub kafka_user_events_topic: String,
+    pub event_source: String,
+    pub jwt_algorithm: JwtAlgorithm,
+    /// Only needed for HS256
     pub jwt_secret: String,
-    pub jwt_expiration_hours: u64,
+    /// PEM private key that signs tokens when the algorithm is RS256 or EdDSA
+    pub jwt_private_key_path: Option<String>,
+    /// PEM public keys still accepted besides the signing key, for rotation
+    pub jwt_verification_key_paths: Vec<String>,
+    pub access_token_ttl_minutes: u64,
+    pub refresh_token_ttl_days: u64,
+    pub signup_role: Role,
+    pub login_max_failures: u32,
+    pub login_ip_max_failures: u32,
+    pub login_failure_window_secs: u64,
+    pub login_lockout_secs: u64,
+    pub login_delay_base_ms: u64,
+    /// Take the client address from `X-Forwarded-For`; only safe behind a proxy
+    pub trust_forwarded_for: bool,
+    pub password_reset_ttl_minutes: u64,
+    pub email_verification_ttl_hours: u64,
+    pub mailer: MailerKind,
+    pub mailer_file_path: String,
+    pub mail_from: String,
+    /// Base of the links put in account emails
+    pub public_base_url: String,
+    pub bootstrap_admin_username: Option<String>,
+    pub bootstrap_admin_password: Option<String>,
+    pub outbox_poll_interval_ms: u64,
+    pub outbox_batch_size: i64,
+    pub outbox_max_backoff_secs: u64,
+    /// How long deleted products stay restorable before they are purged
+    pub product_trash_retention_days: u64,
+    pub product_purge_interval_secs: u64,
+    /// Largest file accepted by `POST /products/import`
+    pub product_import_max_bytes: usize,
+    /// ISO 4217 code for prices given without a currency
+    pub default_currency: String,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self, env::VarError> {
         dotenv().ok();
 
+        let kafka_product_events_topic = env::var("KAFKA_PRODUCT_EVENTS_TOPIC")?;
+
         Ok(Self {
             server_addr: env::var("SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:8000".to_string()),
             database_url: env::var("DATABASE_URL")?,
             database_name: env::var("DATABASE_NAME")?,
+            database_auto_migrate: env::var("DATABASE_AUTO_MIGRATE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .expect("DATABASE_AUTO_MIGRATE must be true or false"),
             kafka_brokers: env::var("KAFKA_BROKERS")?,
-            kafka_product_events_topic: env::var("KAFKA_PRODUCT_EVENTS_TOPIC")?,
-            jwt_secret: env::var("JWT_SECRET")?,
-            jwt_expiration_hours: env::var("JWT_EXPIRATION_HOURS")
-                .unwrap_or_else(|_| "24".to_string())
+            kafka_enabled: env::var("KAFKA_ENABLED")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .expect("KAFKA_ENABLED must be true or false"),
+            kafka_user_events_topic: env::var("KAFKA_USER_EVENTS_TOPIC")
+                .unwrap_or_else(|_| "user_events".to_string()),
+            kafka_dead_letter_topic: env::var("KAFKA_DEAD_LETTER_TOPIC")
+                .unwrap_or_else(|_| format!("{}.dlq", kafka_product_events_topic)),
+            kafka_product_events_topic,
+            kafka_consumer_group: env::var("KAFKA_CONSUMER_GROUP")
+                .unwrap_or_else(|_| "product-event-listener".to_string()),
+            kafka_consumer_max_retries: env::var("KAFKA_CONSUMER_MAX_RETRIES")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .expect("KAFKA_CONSUMER_MAX_RETRIES must be a number"),
+            kafka_consumer_retry_backoff_ms: env::var("KAFKA_CONSUMER_RETRY_BACKOFF_MS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .expect("KAFKA_CONSUMER_RETRY_BACKOFF_MS must be a number"),
+            event_source: env::var("EVENT_SOURCE").unwrap_or_else(|_| "rs-kafka-mongo".to_string()),
+            jwt_algorithm: env::var("JWT_ALGORITHM")
+                .unwrap_or_else(|_| "HS256".to_string())
+                .parse()
+                .expect("JWT_ALGORITHM must be HS256, RS256 or EdDSA"),
+            jwt_secret: env::var("JWT_SECRET").unwrap_or_default(),
+            jwt_private_key_path: env::var("JWT_PRIVATE_KEY_PATH").ok(),
+            jwt_verification_key_paths: env::var("JWT_VERIFICATION_KEY_PATHS")
+                .map(|paths| {
+                    paths
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|path| !path.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            access_token_ttl_minutes: env::var("ACCESS_TOKEN_TTL_MINUTES")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .expect("ACCESS_TOKEN_TTL_MINUTES must be a number"),
+            refresh_token_ttl_days: env::var("REFRESH_TOKEN_TTL_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .expect("REFRESH_TOKEN_TTL_DAYS must be a number"),
+            signup_role: env::var("SIGNUP_ROLE")
+                .unwrap_or_else(|_| "viewer".to_string())
+                .parse()
+                .expect("SIGNUP_ROLE must be admin, editor or viewer"),
+            login_max_failures: env::var("LOGIN_MAX_FAILURES")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .expect("LOGIN_MAX_FAILURES must be a number"),
+            login_ip_max_failures: env::var("LOGIN_IP_MAX_FAILURES")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .expect("LOGIN_IP_MAX_FAILURES must be a number"),
+            login_failure_window_secs: env::var("LOGIN_FAILURE_WINDOW_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .expect("LOGIN_FAILURE_WINDOW_SECS must be a number"),
+            login_lockout_secs: env::var("LOGIN_LOCKOUT_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .expect("LOGIN_LOCKOUT_SECS must be a number"),
+            login_delay_base_ms: env::var("LOGIN_DELAY_BASE_MS")
+                .unwrap_or_else(|_| "250".to_string())
+                .parse()
+                .expect("LOGIN_DELAY_BASE_MS must be a number"),
+            trust_forwarded_for: env::var("TRUST_FORWARDED_FOR")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("TRUST_FORWARDED_FOR must be true or false"),
+            password_reset_ttl_minutes: env::var("PASSWORD_RESET_TTL_MINUTES")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .expect("PASSWORD_RESET_TTL_MINUTES must be a number"),
+            email_verification_ttl_hours: env::var("EMAIL_VERIFICATION_TTL_HOURS")
+                .unwrap_or_else(|_| "48".to_string())
+                .parse()
+                .expect("EMAIL_VERIFICATION_TTL_HOURS must be a number"),
+            mailer: env::var("MAILER")
+                .unwrap_or_else(|_| "mongo".to_string())
+                .parse()
+                .expect("MAILER must be mongo or file"),
+            mailer_file_path: env::var("MAILER_FILE_PATH")
+                .unwrap_or_else(|_| "outgoing_emails.jsonl".to_string()),
+            mail_from: env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string()),
+            public_base_url: env::var("PUBLIC_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8000".to_string()),
+            bootstrap_admin_username: env::var("BOOTSTRAP_ADMIN_USERNAME").ok(),
+            bootstrap_admin_password: env::var("BOOTSTRAP_ADMIN_PASSWORD").ok(),
+            outbox_poll_interval_ms: env::var("OUTBOX_POLL_INTERVAL_MS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .expect("OUTBOX_POLL_INTERVAL_MS must be a number"),
+            outbox_batch_size: env::var("OUTBOX_BATCH_SIZE")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .expect("OUTBOX_BATCH_SIZE must be a number"),
+            outbox_max_backoff_secs: env::var("OUTBOX_MAX_BACKOFF_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .expect("OUTBOX_MAX_BACKOFF_SECS must be a number"),
+            product_trash_retention_days: env::var("PRODUCT_TRASH_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .expect("PRODUCT_TRASH_RETENTION_DAYS must be a number"),
+            product_purge_interval_secs: env::var("PRODUCT_PURGE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .expect("PRODUCT_PURGE_INTERVAL_SECS must be a number"),
+            product_import_max_bytes: env::var("PRODUCT_IMPORT_MAX_BYTES")
+                .unwrap_or_else(|_| "10485760".to_string())
                 .parse()
-                .expect("JWT_EXPIRATION_HOURS must be a number"),
+                .expect("PRODUCT_IMPORT_MAX_BYTES must be a number"),
+            default_currency: Currency::from_code(
+                &env::var("DEFAULT_CURRENCY").unwrap_or_else(|_| "USD".to_string()),
+            )
+            .expect("DEFAULT_CURRENCY must be a supported ISO 4217 code")
+            .code
+            .to_string(),
         })
     }
 }
\ No newline at end of file
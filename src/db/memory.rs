@@ -0,0 +1,950 @@
+use crate::db::mongo::MongoError;
+use crate::db::repository::{
+    ApiKeyRepository, DeadLetterRepository, ImportJobRepository, MailRepository, MessageRepository,
+    OutboxRepository, ProductRepository, ProductStream, TokenRepository, UserRepository,
+};
+use crate::mail::models::OutgoingEmail;
+use crate::dead_letter::models::DeadLetter;
+use crate::outbox::models::{OutboxEntry, OutboxStatus, ProductOutboxFactory, UserOutboxFactory};
+use crate::products::models::{
+    ImportJob, Product, ProductFilter, ProductQuery, ProductSearchQuery, ProductWrite,
+    ProductWriteOutcome, ScoredProduct, SortOrder,
+};
+use crate::products::utils::search_terms;
+use crate::{
+    auth::models::{
+        AccountToken, ApiKey, RefreshToken, RevokedToken, Role, TokenPurpose, User,
+    },
+    message::models::Message,
+};
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use serde::{Serialize, de::DeserializeOwned};
+use chrono::{DateTime, Utc};
+use mongodb::bson::{self, Bson, Document, oid::ObjectId};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Default)]
+struct Store {
+    users: BTreeMap<ObjectId, User>,
+    refresh_tokens: BTreeMap<ObjectId, RefreshToken>,
+    revoked_tokens: BTreeMap<String, RevokedToken>,
+    account_tokens: BTreeMap<ObjectId, AccountToken>,
+    api_keys: BTreeMap<ObjectId, ApiKey>,
+    outgoing_emails: BTreeMap<ObjectId, OutgoingEmail>,
+    products: BTreeMap<ObjectId, Product>,
+    import_jobs: BTreeMap<ObjectId, ImportJob>,
+    messages: BTreeMap<ObjectId, Message>,
+    outbox: BTreeMap<ObjectId, OutboxEntry>,
+    dead_letters: BTreeMap<ObjectId, DeadLetter>,
+}
+
+impl Store {
+    fn push_outbox(&mut self, mut entry: OutboxEntry) {
+        let id = *entry._id.get_or_insert_with(ObjectId::new);
+        self.outbox.insert(id, entry);
+    }
+}
+
+/// Repository backed by process memory. Data is lost when the last clone is
+/// dropped, which makes it suitable for tests and local demos.
+#[derive(Clone, Default)]
+pub struct InMemoryRepo {
+    store: Arc<RwLock<Store>>,
+}
+
+impl InMemoryRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Store> {
+        self.store.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Store> {
+        self.store.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn apply_set<T>(value: &T, update_doc: Document) -> Result<T, MongoError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut document = bson::to_document(value).map_err(mongodb::error::Error::from)?;
+    document.extend(update_doc);
+    Ok(bson::from_document(document).map_err(mongodb::error::Error::from)?)
+}
+
+/// Whether a product other than `id` holds `sku`, standing in for the unique
+/// SKU index.
+fn sku_taken(products: &BTreeMap<ObjectId, Product>, sku: Option<&str>, id: ObjectId) -> bool {
+    sku.is_some_and(|sku| {
+        products
+            .values()
+            .any(|product| product.sku.as_deref() == Some(sku) && product._id != Some(id))
+    })
+}
+
+fn compare_bson(a: &Bson, b: &Bson) -> Ordering {
+    match (a, b) {
+        (Bson::String(a), Bson::String(b)) => a.cmp(b),
+        (Bson::Double(a), Bson::Double(b)) => a.total_cmp(b),
+        (Bson::Int64(a), Bson::Int64(b)) => a.cmp(b),
+        (Bson::DateTime(a), Bson::DateTime(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+fn matches_filter(product: &Product, filter: &ProductFilter) -> bool {
+    filter
+        .min_price_minor
+        .is_none_or(|min| product.price_minor >= min)
+        && filter
+            .max_price_minor
+            .is_none_or(|max| product.price_minor <= max)
+        && filter
+            .currency
+            .as_ref()
+            .is_none_or(|currency| &product.currency == currency)
+        && filter.name_contains.as_ref().is_none_or(|name| {
+            product.name.to_lowercase().contains(&name.to_lowercase())
+        })
+        && filter
+            .created_after
+            .is_none_or(|after| product.created_at > after)
+        && filter
+            .owner_id
+            .is_none_or(|owner| product.owner_id == Some(owner))
+        && product.deleted_at.is_some() == filter.trashed
+}
+
+/// Rough stand-in for MongoDB's text score: weighted count of query terms
+/// found in the name and description.
+fn text_score(product: &Product, terms: &[String]) -> f64 {
+    let name = product.name.to_lowercase();
+    let description = product.description.to_lowercase();
+    terms
+        .iter()
+        .map(|term| {
+            let term = term.to_lowercase();
+            3.0 * name.matches(&term).count() as f64 + description.matches(&term).count() as f64
+        })
+        .sum()
+}
+
+#[async_trait]
+impl UserRepository for InMemoryRepo {
+    async fn create_user(&self, mut new_user: User) -> Result<ObjectId, MongoError> {
+        let mut store = self.write();
+        if store.users.values().any(|user| user.username == new_user.username) {
+            return Err(MongoError::DuplicateKey(new_user.username));
+        }
+        let id = *new_user._id.get_or_insert_with(ObjectId::new);
+        store.users.insert(id, new_user);
+        Ok(id)
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MongoError> {
+        Ok(self
+            .read()
+            .users
+            .values()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, MongoError> {
+        Ok(self.read().users.get(&id).cloned())
+    }
+
+    async fn find_users_by_email(&self, email: &str) -> Result<Vec<User>, MongoError> {
+        Ok(self
+            .read()
+            .users
+            .values()
+            .filter(|user| user.email.as_deref() == Some(email))
+            .cloned()
+            .collect())
+    }
+
+    async fn mark_email_verified(&self, id: ObjectId, email: &str) -> Result<bool, MongoError> {
+        let mut store = self.write();
+        match store.users.get_mut(&id) {
+            Some(user) if user.email.as_deref() == Some(email) => {
+                user.email_verified = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn update_user_profile(
+        &self,
+        id: ObjectId,
+        update: Document,
+        event: UserOutboxFactory,
+    ) -> Result<Option<User>, MongoError> {
+        let mut store = self.write();
+        let Some(user) = store.users.get(&id) else {
+            return Ok(None);
+        };
+        let updated_user = apply_set(user, update)?;
+        let entry = event(&updated_user)?;
+        store.users.insert(id, updated_user.clone());
+        store.push_outbox(entry);
+        Ok(Some(updated_user))
+    }
+
+    async fn update_user_password(
+        &self,
+        id: ObjectId,
+        password_hash: String,
+        event: OutboxEntry,
+    ) -> Result<bool, MongoError> {
+        let mut store = self.write();
+        let Some(user) = store.users.get_mut(&id) else {
+            return Ok(false);
+        };
+        user.password_hash = password_hash;
+        store.push_outbox(event);
+        Ok(true)
+    }
+
+    async fn delete_user(&self, id: ObjectId, event: OutboxEntry) -> Result<bool, MongoError> {
+        let mut store = self.write();
+        if store.users.remove(&id).is_none() {
+            return Ok(false);
+        }
+        store.push_outbox(event);
+        Ok(true)
+    }
+
+    async fn record_failed_login(
+        &self,
+        id: ObjectId,
+        now: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+    ) -> Result<Option<User>, MongoError> {
+        let mut store = self.write();
+        Ok(store.users.get_mut(&id).map(|user| {
+            user.failed_login_count = match user.last_failed_login_at {
+                Some(at) if at >= window_start => user.failed_login_count + 1,
+                _ => 1,
+            };
+            user.last_failed_login_at = Some(now);
+            user.clone()
+        }))
+    }
+
+    async fn lock_user(&self, id: ObjectId, until: DateTime<Utc>) -> Result<(), MongoError> {
+        if let Some(user) = self.write().users.get_mut(&id) {
+            user.locked_until = Some(until);
+        }
+        Ok(())
+    }
+
+    async fn reset_failed_logins(&self, id: ObjectId) -> Result<(), MongoError> {
+        if let Some(user) = self.write().users.get_mut(&id) {
+            user.failed_login_count = 0;
+            user.last_failed_login_at = None;
+            user.locked_until = None;
+        }
+        Ok(())
+    }
+
+    async fn bump_token_epoch(&self, id: ObjectId) -> Result<(), MongoError> {
+        if let Some(user) = self.write().users.get_mut(&id) {
+            user.token_epoch += 1;
+        }
+        Ok(())
+    }
+
+    async fn update_user_role(
+        &self,
+        username: &str,
+        role: Role,
+    ) -> Result<Option<User>, MongoError> {
+        let mut store = self.write();
+        let user = store.users.values_mut().find(|user| user.username == username);
+        Ok(user.map(|user| {
+            user.role = role;
+            user.clone()
+        }))
+    }
+}
+
+#[async_trait]
+impl TokenRepository for InMemoryRepo {
+    async fn create_refresh_token(&self, mut token: RefreshToken) -> Result<ObjectId, MongoError> {
+        let id = *token._id.get_or_insert_with(ObjectId::new);
+        self.write().refresh_tokens.insert(id, token);
+        Ok(id)
+    }
+
+    async fn find_refresh_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, MongoError> {
+        Ok(self
+            .read()
+            .refresh_tokens
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn claim_refresh_token(&self, id: ObjectId) -> Result<bool, MongoError> {
+        let mut store = self.write();
+        match store.refresh_tokens.get_mut(&id) {
+            Some(token) if token.used_at.is_none() && token.revoked_at.is_none() => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<u64, MongoError> {
+        let now = Utc::now();
+        let mut revoked = 0;
+        for token in self.write().refresh_tokens.values_mut() {
+            if token.family_id == family_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+
+    async fn revoke_user_refresh_tokens(&self, user_id: ObjectId) -> Result<u64, MongoError> {
+        let now = Utc::now();
+        let mut revoked = 0;
+        for token in self.write().refresh_tokens.values_mut() {
+            if token.user_id == user_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+
+    async fn revoke_access_token(&self, token: RevokedToken) -> Result<(), MongoError> {
+        self.write().revoked_tokens.insert(token.jti.clone(), token);
+        Ok(())
+    }
+
+    async fn is_access_token_revoked(&self, jti: &str) -> Result<bool, MongoError> {
+        Ok(self.read().revoked_tokens.contains_key(jti))
+    }
+
+    async fn create_account_token(&self, mut token: AccountToken) -> Result<ObjectId, MongoError> {
+        let mut store = self.write();
+        store.account_tokens.retain(|_, existing| {
+            existing.user_id != token.user_id
+                || existing.purpose != token.purpose
+                || existing.used_at.is_some()
+        });
+        let id = *token._id.get_or_insert_with(ObjectId::new);
+        store.account_tokens.insert(id, token);
+        Ok(id)
+    }
+
+    async fn claim_account_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>, MongoError> {
+        let mut store = self.write();
+        let token = store.account_tokens.values_mut().find(|token| {
+            token.token_hash == token_hash
+                && token.purpose == purpose
+                && token.used_at.is_none()
+                && token.expires_at > now
+        });
+        Ok(token.map(|token| {
+            token.used_at = Some(now);
+            token.clone()
+        }))
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for InMemoryRepo {
+    async fn create_api_key(&self, mut key: ApiKey) -> Result<ObjectId, MongoError> {
+        let id = *key._id.get_or_insert_with(ObjectId::new);
+        self.write().api_keys.insert(id, key);
+        Ok(id)
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, MongoError> {
+        Ok(self
+            .read()
+            .api_keys
+            .values()
+            .find(|key| key.key_hash == key_hash)
+            .cloned())
+    }
+
+    async fn list_api_keys(&self, user_id: ObjectId) -> Result<Vec<ApiKey>, MongoError> {
+        // ObjectIds grow over time, so reverse id order is newest first.
+        Ok(self
+            .read()
+            .api_keys
+            .values()
+            .rev()
+            .filter(|key| key.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn revoke_api_key(&self, id: ObjectId, user_id: ObjectId) -> Result<bool, MongoError> {
+        let mut store = self.write();
+        match store.api_keys.get_mut(&id) {
+            Some(key) if key.user_id == user_id && key.revoked_at.is_none() => {
+                key.revoked_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_user_api_keys(&self, user_id: ObjectId) -> Result<u64, MongoError> {
+        let now = Utc::now();
+        let mut revoked = 0;
+        for key in self.write().api_keys.values_mut() {
+            if key.user_id == user_id && key.revoked_at.is_none() {
+                key.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+
+    async fn touch_api_key(
+        &self,
+        id: ObjectId,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<(), MongoError> {
+        let mut store = self.write();
+        let stale = store
+            .api_keys
+            .get_mut(&id)
+            .filter(|key| key.last_used_at.is_none_or(|at| at < stale_before));
+        if let Some(key) = stale {
+            key.last_used_at = Some(now);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MailRepository for InMemoryRepo {
+    async fn enqueue_email(&self, mut email: OutgoingEmail) -> Result<ObjectId, MongoError> {
+        let id = *email._id.get_or_insert_with(ObjectId::new);
+        self.write().outgoing_emails.insert(id, email);
+        Ok(id)
+    }
+}
+
+#[async_trait]
+impl ProductRepository for InMemoryRepo {
+    async fn create_product(
+        &self,
+        mut new_product: Product,
+        event: OutboxEntry,
+    ) -> Result<ObjectId, MongoError> {
+        let id = *new_product._id.get_or_insert_with(ObjectId::new);
+        let mut store = self.write();
+        if sku_taken(&store.products, new_product.sku.as_deref(), id) {
+            return Err(MongoError::DuplicateKey("sku".to_string()));
+        }
+        store.products.insert(id, new_product);
+        store.push_outbox(event);
+        Ok(id)
+    }
+
+    async fn find_product_by_id(&self, id: ObjectId) -> Result<Option<Product>, MongoError> {
+        Ok(self
+            .read()
+            .products
+            .get(&id)
+            .filter(|product| product.deleted_at.is_none())
+            .cloned())
+    }
+
+    async fn find_trashed_product(&self, id: ObjectId) -> Result<Option<Product>, MongoError> {
+        Ok(self
+            .read()
+            .products
+            .get(&id)
+            .filter(|product| product.deleted_at.is_some())
+            .cloned())
+    }
+
+    async fn find_products_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Product>, MongoError> {
+        let store = self.read();
+        Ok(ids
+            .iter()
+            .filter_map(|id| store.products.get(id))
+            .filter(|product| product.deleted_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn find_products_by_skus(&self, skus: &[String]) -> Result<Vec<Product>, MongoError> {
+        Ok(self
+            .read()
+            .products
+            .values()
+            .filter(|product| product.sku.as_ref().is_some_and(|sku| skus.contains(sku)))
+            .cloned()
+            .collect())
+    }
+
+    async fn find_products(&self, query: &ProductQuery) -> Result<Vec<Product>, MongoError> {
+        let compare = |a: &(Bson, ObjectId), b: &(Bson, ObjectId)| {
+            let ordering = compare_bson(&a.0, &b.0).then(a.1.cmp(&b.1));
+            match query.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        };
+        let after = query.after.as_ref().map(|after| (after.value.clone(), after.id));
+
+        let mut products: Vec<((Bson, ObjectId), Product)> = self
+            .read()
+            .products
+            .values()
+            .filter(|product| matches_filter(product, &query.filter))
+            .map(|product| {
+                let key = (product.sort_value(query.sort), product._id.unwrap_or_default());
+                (key, product.clone())
+            })
+            .filter(|(key, _)| {
+                after
+                    .as_ref()
+                    .is_none_or(|after| compare(key, after) == Ordering::Greater)
+            })
+            .collect();
+        products.sort_by(|a, b| compare(&a.0, &b.0));
+
+        Ok(products
+            .into_iter()
+            .take(query.limit)
+            .map(|(_, product)| product)
+            .collect())
+    }
+
+    async fn stream_products(&self, filter: &ProductFilter) -> Result<ProductStream, MongoError> {
+        let products: Vec<Product> = self
+            .read()
+            .products
+            .values()
+            .filter(|product| matches_filter(product, filter))
+            .cloned()
+            .collect();
+        Ok(stream::iter(products.into_iter().map(Ok)).boxed())
+    }
+
+    async fn search_products(
+        &self,
+        query: &ProductSearchQuery,
+    ) -> Result<Vec<ScoredProduct>, MongoError> {
+        let terms = search_terms(&query.text);
+        let mut hits: Vec<ScoredProduct> = self
+            .read()
+            .products
+            .values()
+            .filter(|product| product.deleted_at.is_none())
+            .map(|product| ScoredProduct {
+                score: text_score(product, &terms),
+                product: product.clone(),
+            })
+            .filter(|hit| hit.score > 0.0)
+            .filter(|hit| {
+                query.after.as_ref().is_none_or(|after| {
+                    hit.score < after.score
+                        || (hit.score == after.score
+                            && hit.product._id.is_some_and(|id| id > after.id))
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.product._id.cmp(&b.product._id))
+        });
+        hits.truncate(query.limit);
+        Ok(hits)
+    }
+
+    async fn update_product(
+        &self,
+        id: ObjectId,
+        expected_version: i64,
+        update_doc: Document,
+        event: ProductOutboxFactory,
+    ) -> Result<Option<Product>, MongoError> {
+        let mut store = self.write();
+        let Some(product) = store
+            .products
+            .get(&id)
+            .filter(|product| product.deleted_at.is_none() && product.version == expected_version)
+        else {
+            return Ok(None);
+        };
+        let mut updated_product = apply_set(product, update_doc)?;
+        if sku_taken(&store.products, updated_product.sku.as_deref(), id) {
+            return Err(MongoError::DuplicateKey("sku".to_string()));
+        }
+        updated_product.version += 1;
+        let entry = event(&updated_product)?;
+        store.products.insert(id, updated_product.clone());
+        store.push_outbox(entry);
+        Ok(Some(updated_product))
+    }
+
+    async fn delete_product(
+        &self,
+        id: ObjectId,
+        deleted_by: ObjectId,
+        event: OutboxEntry,
+    ) -> Result<bool, MongoError> {
+        let mut store = self.write();
+        let Some(product) = store
+            .products
+            .get_mut(&id)
+            .filter(|product| product.deleted_at.is_none())
+        else {
+            return Ok(false);
+        };
+        let now = Utc::now();
+        product.deleted_at = Some(now);
+        product.deleted_by = Some(deleted_by);
+        product.updated_at = now;
+        product.version += 1;
+        store.push_outbox(event);
+        Ok(true)
+    }
+
+    async fn restore_product(
+        &self,
+        id: ObjectId,
+        restored_by: ObjectId,
+        event: ProductOutboxFactory,
+    ) -> Result<Option<Product>, MongoError> {
+        let mut store = self.write();
+        let Some(product) = store
+            .products
+            .get(&id)
+            .filter(|product| product.deleted_at.is_some())
+        else {
+            return Ok(None);
+        };
+        let mut restored = product.clone();
+        restored.deleted_at = None;
+        restored.deleted_by = None;
+        restored.updated_at = Utc::now();
+        restored.updated_by = Some(restored_by);
+        restored.version += 1;
+        let entry = event(&restored)?;
+        store.products.insert(id, restored.clone());
+        store.push_outbox(entry);
+        Ok(Some(restored))
+    }
+
+    async fn write_products(
+        &self,
+        writes: Vec<ProductWrite>,
+    ) -> Result<Vec<ProductWriteOutcome>, MongoError> {
+        let mut store = self.write();
+        // Work on a copy so a failing write leaves nothing behind, like the
+        // transaction in the MongoDB backend. A SKU clash only skips its own
+        // write, as the MongoDB backend's retry does.
+        let mut products = store.products.clone();
+        let mut entries = Vec::new();
+        let mut outcomes = Vec::with_capacity(writes.len());
+        for write in writes {
+            let outcome = match write {
+                ProductWrite::Create { mut product, event } => {
+                    let id = *product._id.get_or_insert_with(ObjectId::new);
+                    if sku_taken(&products, product.sku.as_deref(), id) {
+                        ProductWriteOutcome::SkuTaken
+                    } else {
+                        products.insert(id, product.clone());
+                        entries.push(event);
+                        ProductWriteOutcome::Written(Box::new(product))
+                    }
+                }
+                ProductWrite::Update {
+                    id,
+                    expected_version,
+                    update,
+                    event,
+                } => {
+                    let current = products.get(&id).filter(|product| {
+                        product.deleted_at.is_none() && product.version == expected_version
+                    });
+                    match current {
+                        None => ProductWriteOutcome::Stale,
+                        Some(product) => {
+                            let mut updated = apply_set(product, update)?;
+                            if sku_taken(&products, updated.sku.as_deref(), id) {
+                                ProductWriteOutcome::SkuTaken
+                            } else {
+                                updated.version += 1;
+                                entries.push(event(&updated)?);
+                                products.insert(id, updated.clone());
+                                ProductWriteOutcome::Written(Box::new(updated))
+                            }
+                        }
+                    }
+                }
+                ProductWrite::Delete {
+                    id,
+                    expected_version,
+                    deleted_by,
+                    event,
+                } => {
+                    let current = products.get_mut(&id).filter(|product| {
+                        product.deleted_at.is_none() && product.version == expected_version
+                    });
+                    match current {
+                        None => ProductWriteOutcome::Stale,
+                        Some(product) => {
+                            let now = Utc::now();
+                            product.deleted_at = Some(now);
+                            product.deleted_by = Some(deleted_by);
+                            product.updated_at = now;
+                            product.version += 1;
+                            entries.push(event);
+                            ProductWriteOutcome::Written(Box::new(product.clone()))
+                        }
+                    }
+                }
+            };
+            outcomes.push(outcome);
+        }
+        store.products = products;
+        for entry in entries {
+            store.push_outbox(entry);
+        }
+        Ok(outcomes)
+    }
+
+    async fn purge_trashed_products(
+        &self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<u64, MongoError> {
+        let mut store = self.write();
+        let before = store.products.len();
+        store
+            .products
+            .retain(|_, product| product.deleted_at.is_none_or(|at| at >= deleted_before));
+        Ok((before - store.products.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl ImportJobRepository for InMemoryRepo {
+    async fn create_import_job(&self, mut job: ImportJob) -> Result<ObjectId, MongoError> {
+        let id = *job._id.get_or_insert_with(ObjectId::new);
+        self.write().import_jobs.insert(id, job);
+        Ok(id)
+    }
+
+    async fn find_import_job(&self, id: ObjectId) -> Result<Option<ImportJob>, MongoError> {
+        Ok(self.read().import_jobs.get(&id).cloned())
+    }
+
+    async fn save_import_job(&self, job: &ImportJob) -> Result<(), MongoError> {
+        let id = job._id.ok_or(MongoError::NotFound)?;
+        self.write().import_jobs.insert(id, job.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageRepository for InMemoryRepo {
+    async fn find_all_message(&self) -> Result<Vec<Message>, MongoError> {
+        Ok(self.read().messages.values().cloned().collect())
+    }
+
+    async fn create_message(&self, mut new_message: Message) -> Result<ObjectId, MongoError> {
+        let id = *new_message._id.get_or_insert_with(ObjectId::new);
+        self.write().messages.insert(id, new_message);
+        Ok(id)
+    }
+
+    async fn create_message_if_absent(&self, mut new_message: Message) -> Result<bool, MongoError> {
+        let mut store = self.write();
+        if let Some(event_id) = &new_message.event_id {
+            let exists = store
+                .messages
+                .values()
+                .any(|message| message.event_id.as_ref() == Some(event_id));
+            if exists {
+                return Ok(false);
+            }
+        }
+        let id = *new_message._id.get_or_insert_with(ObjectId::new);
+        store.messages.insert(id, new_message);
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for InMemoryRepo {
+    async fn find_due_outbox_entries(&self, limit: i64) -> Result<Vec<OutboxEntry>, MongoError> {
+        let now = Utc::now();
+        let store = self.read();
+        let pending = || {
+            store
+                .outbox
+                .values()
+                .filter(|entry| entry.status == OutboxStatus::Pending)
+        };
+        let waiting_keys: HashSet<&str> = pending()
+            .filter(|entry| entry.next_attempt_at > now)
+            .map(|entry| entry.key.as_str())
+            .collect();
+        let mut entries: Vec<OutboxEntry> = pending()
+            .filter(|entry| !waiting_keys.contains(entry.key.as_str()))
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| (entry.created_at, entry._id));
+        entries.truncate(usize::try_from(limit).unwrap_or(0));
+        Ok(entries)
+    }
+
+    async fn mark_outbox_delivered(&self, id: ObjectId) -> Result<(), MongoError> {
+        if let Some(entry) = self.write().outbox.get_mut(&id) {
+            entry.status = OutboxStatus::Delivered;
+            entry.delivered_at = Some(Utc::now());
+            entry.last_error = None;
+            entry.attempts += 1;
+        }
+        Ok(())
+    }
+
+    async fn mark_outbox_failed(
+        &self,
+        id: ObjectId,
+        error: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), MongoError> {
+        if let Some(entry) = self.write().outbox.get_mut(&id) {
+            entry.last_error = Some(error.to_string());
+            entry.next_attempt_at = next_attempt_at;
+            entry.attempts += 1;
+        }
+        Ok(())
+    }
+
+    async fn count_pending_outbox(&self) -> Result<u64, MongoError> {
+        Ok(self
+            .read()
+            .outbox
+            .values()
+            .filter(|entry| entry.status == OutboxStatus::Pending)
+            .count() as u64)
+    }
+}
+
+#[async_trait]
+impl DeadLetterRepository for InMemoryRepo {
+    async fn create_dead_letter(&self, mut dead_letter: DeadLetter) -> Result<ObjectId, MongoError> {
+        let id = *dead_letter._id.get_or_insert_with(ObjectId::new);
+        self.write().dead_letters.insert(id, dead_letter);
+        Ok(id)
+    }
+
+    async fn find_all_dead_letters(&self) -> Result<Vec<DeadLetter>, MongoError> {
+        let mut dead_letters: Vec<DeadLetter> =
+            self.read().dead_letters.values().cloned().collect();
+        dead_letters.sort_by_key(|dead_letter| std::cmp::Reverse(dead_letter.failed_at));
+        Ok(dead_letters)
+    }
+
+    async fn find_dead_letter_by_id(&self, id: ObjectId) -> Result<Option<DeadLetter>, MongoError> {
+        Ok(self.read().dead_letters.get(&id).cloned())
+    }
+
+    async fn mark_dead_letter_redriven(&self, id: ObjectId) -> Result<bool, MongoError> {
+        match self.write().dead_letters.get_mut(&id) {
+            Some(dead_letter) => {
+                dead_letter.redriven_at = Some(Utc::now());
+                dead_letter.redrive_count += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kafka::publisher::EventRecord;
+    use crate::products::{handlers::new_product, models::CreateProductRequest};
+
+    fn product(sku: &str) -> Product {
+        let request = CreateProductRequest {
+            sku: Some(sku.to_string()),
+            name: sku.to_string(),
+            description: String::new(),
+            price: "1.00".to_string(),
+            currency: None,
+        };
+        new_product(request, ObjectId::new(), "USD").unwrap()
+    }
+
+    fn entry() -> OutboxEntry {
+        OutboxEntry::new(EventRecord {
+            topic: "products".to_string(),
+            key: "key".to_string(),
+            payload: Vec::new(),
+            headers: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn write_products_skips_only_writes_whose_sku_was_taken() {
+        let repo = InMemoryRepo::new();
+        // Taken after the caller's check, before its batch is written.
+        repo.create_product(product("TAKEN"), entry()).await.unwrap();
+
+        let writes = vec![
+            ProductWrite::Create {
+                product: product("FREE"),
+                event: entry(),
+            },
+            ProductWrite::Create {
+                product: product("TAKEN"),
+                event: entry(),
+            },
+        ];
+        let outcomes = repo.write_products(writes).await.unwrap();
+
+        assert!(matches!(
+            &outcomes[0],
+            ProductWriteOutcome::Written(product) if product.sku.as_deref() == Some("FREE")
+        ));
+        assert!(matches!(outcomes[1], ProductWriteOutcome::SkuTaken));
+        let skus: Vec<String> = repo
+            .find_products_by_skus(&["FREE".to_string(), "TAKEN".to_string()])
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|product| product.sku)
+            .collect();
+        assert_eq!(skus.len(), 2);
+        // One event for the original product and one for the new write.
+        assert_eq!(repo.count_pending_outbox().await.unwrap(), 2);
+    }
+}
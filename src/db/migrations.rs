@@ -0,0 +1,605 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{Document, doc},
+    error::ErrorKind,
+    options::IndexOptions,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config::Config;
+use crate::db::mongo::{MongoError, is_duplicate_key};
+use crate::products::money::Currency;
+
+pub const MIGRATIONS_COLLECTION: &str = "schema_migrations";
+const INDEX_NOT_FOUND_CODE: i32 = 27;
+
+/// A single schema or data change. Migrations run once each, in ascending
+/// `version` order, and must be safe to re-run: two instances booting at the
+/// same time may both apply a migration before either records it.
+#[async_trait]
+pub trait Migration: Send + Sync {
+    fn version(&self) -> i32;
+    fn name(&self) -> &'static str;
+    async fn up(&self, db: &Database) -> Result<(), MongoError>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MigrationRecord {
+    #[serde(rename = "_id")]
+    pub version: i32,
+    pub name: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub applied_at: DateTime<Utc>,
+    pub duration_ms: i64,
+}
+
+/// Every migration the application knows about, oldest first. Append new
+/// migrations with the next version number; never renumber or edit one that
+/// has shipped. Migrations that depend on settings take them from `config`.
+pub fn migrations(config: &Config) -> Vec<Box<dyn Migration>> {
+    vec![
+        Box::new(UniqueUsername),
+        Box::new(ProductQueryIndexes),
+        Box::new(UniqueMessageEventId),
+        Box::new(BackfillMessageReceivedAt),
+        Box::new(BackfillUserRoles),
+        Box::new(AuthTokenIndexes),
+        Box::new(RefreshTokenUserIndex),
+        Box::new(AccountTokenIndexes),
+        Box::new(ApiKeyIndexes),
+        Box::new(ProductOwnerIndex),
+        Box::new(BackfillProductVersions),
+        Box::new(ProductTrashIndex),
+        Box::new(ProductSkuIndexes),
+        Box::new(ProductPricesInMinorUnits {
+            default_currency: Currency::from_code(&config.default_currency)
+                .expect("DEFAULT_CURRENCY must be a supported ISO 4217 code"),
+        }),
+    ]
+}
+
+pub struct MigrationRunner {
+    db: Database,
+    migrations: Vec<Box<dyn Migration>>,
+}
+
+impl MigrationRunner {
+    pub fn new(db: Database, config: &Config) -> Self {
+        Self::with_migrations(db, migrations(config))
+    }
+
+    pub fn with_migrations(db: Database, mut migrations: Vec<Box<dyn Migration>>) -> Self {
+        migrations.sort_by_key(|migration| migration.version());
+        Self { db, migrations }
+    }
+
+    fn records(&self) -> Collection<MigrationRecord> {
+        self.db.collection::<MigrationRecord>(MIGRATIONS_COLLECTION)
+    }
+
+    /// Migrations already recorded as applied, oldest first.
+    pub async fn applied(&self) -> Result<Vec<MigrationRecord>, MongoError> {
+        let cursor = self.records().find(doc! {}).sort(doc! { "_id": 1 }).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    /// Applies every migration that has not been recorded yet and returns the
+    /// records written by this run.
+    pub async fn run(&self) -> Result<Vec<MigrationRecord>, MongoError> {
+        let applied: Vec<i32> = self
+            .applied()
+            .await?
+            .into_iter()
+            .map(|record| record.version)
+            .collect();
+
+        let mut newly_applied = Vec::new();
+        for migration in &self.migrations {
+            if applied.contains(&migration.version()) {
+                continue;
+            }
+
+            info!(
+                "Applying migration {} ({})",
+                migration.version(),
+                migration.name()
+            );
+            let started = Instant::now();
+            migration.up(&self.db).await?;
+            let record = MigrationRecord {
+                version: migration.version(),
+                name: migration.name().to_string(),
+                applied_at: Utc::now(),
+                duration_ms: started.elapsed().as_millis() as i64,
+            };
+
+            match self.records().insert_one(&record).await {
+                Ok(_) => newly_applied.push(record),
+                // Another instance recorded it first; the work is idempotent.
+                Err(e) if is_duplicate_key(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if newly_applied.is_empty() {
+            info!("Database schema is up to date");
+        }
+        Ok(newly_applied)
+    }
+}
+
+fn named_index(keys: Document, name: &str) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().name(name.to_string()).build())
+        .build()
+}
+
+struct UniqueUsername;
+
+#[async_trait]
+impl Migration for UniqueUsername {
+    fn version(&self) -> i32 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "users_unique_username"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        let index = IndexModel::builder()
+            .keys(doc! { "username": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("username_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+        db.collection::<Document>("users").create_index(index).await?;
+        Ok(())
+    }
+}
+
+struct ProductQueryIndexes;
+
+#[async_trait]
+impl Migration for ProductQueryIndexes {
+    fn version(&self) -> i32 {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "product_query_indexes"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        let text_index = IndexModel::builder()
+            .keys(doc! { "name": "text", "description": "text" })
+            .options(
+                IndexOptions::builder()
+                    .name("product_text".to_string())
+                    .weights(doc! { "name": 3, "description": 1 })
+                    .build(),
+            )
+            .build();
+        // Listing sorts on `field, _id`, so the tie-breaker is part of the key.
+        let indexes = vec![
+            text_index,
+            named_index(doc! { "price": 1, "_id": 1 }, "price_id"),
+            named_index(doc! { "created_at": 1, "_id": 1 }, "created_at_id"),
+        ];
+        db.collection::<Document>("products")
+            .create_indexes(indexes)
+            .await?;
+        Ok(())
+    }
+}
+
+struct UniqueMessageEventId;
+
+#[async_trait]
+impl Migration for UniqueMessageEventId {
+    fn version(&self) -> i32 {
+        3
+    }
+
+    fn name(&self) -> &'static str {
+        "messages_unique_event_id"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // Messages recorded before events carried an id have `event_id: null`.
+        let index = IndexModel::builder()
+            .keys(doc! { "event_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("event_id_unique".to_string())
+                    .unique(true)
+                    .partial_filter_expression(doc! { "event_id": { "$type": "string" } })
+                    .build(),
+            )
+            .build();
+        db.collection::<Document>("messages")
+            .create_index(index)
+            .await?;
+        Ok(())
+    }
+}
+
+struct BackfillMessageReceivedAt;
+
+#[async_trait]
+impl Migration for BackfillMessageReceivedAt {
+    fn version(&self) -> i32 {
+        4
+    }
+
+    fn name(&self) -> &'static str {
+        "backfill_message_received_at"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // The ObjectId timestamp is the best record of when old messages arrived.
+        let result = db
+            .collection::<Document>("messages")
+            .update_many(
+                doc! { "received_at": null },
+                vec![doc! { "$set": { "received_at": { "$toDate": "$_id" } } }],
+            )
+            .await?;
+        info!("Backfilled received_at on {} messages", result.modified_count);
+        Ok(())
+    }
+}
+
+struct BackfillUserRoles;
+
+#[async_trait]
+impl Migration for BackfillUserRoles {
+    fn version(&self) -> i32 {
+        5
+    }
+
+    fn name(&self) -> &'static str {
+        "backfill_user_roles"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // Accounts created before roles existed could already write products;
+        // keep that access rather than silently demoting them to viewers.
+        let result = db
+            .collection::<Document>("users")
+            .update_many(
+                doc! { "role": { "$exists": false } },
+                doc! { "$set": { "role": "editor" } },
+            )
+            .await?;
+        info!("Assigned the editor role to {} existing users", result.modified_count);
+        Ok(())
+    }
+}
+
+struct AuthTokenIndexes;
+
+#[async_trait]
+impl Migration for AuthTokenIndexes {
+    fn version(&self) -> i32 {
+        6
+    }
+
+    fn name(&self) -> &'static str {
+        "auth_token_indexes"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // Expired refresh tokens and denylist entries are useless, so let the
+        // TTL monitor drop them instead of a cleanup job.
+        let expires = || {
+            IndexOptions::builder()
+                .name("expires_at_ttl".to_string())
+                .expire_after(Duration::ZERO)
+                .build()
+        };
+        let refresh_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "token_hash": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name("token_hash_unique".to_string())
+                        .unique(true)
+                        .build(),
+                )
+                .build(),
+            named_index(doc! { "family_id": 1 }, "family_id"),
+            IndexModel::builder()
+                .keys(doc! { "expires_at": 1 })
+                .options(expires())
+                .build(),
+        ];
+        db.collection::<Document>("refresh_tokens")
+            .create_indexes(refresh_indexes)
+            .await?;
+        let revoked_index = IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(expires())
+            .build();
+        db.collection::<Document>("revoked_tokens")
+            .create_index(revoked_index)
+            .await?;
+        Ok(())
+    }
+}
+
+struct RefreshTokenUserIndex;
+
+#[async_trait]
+impl Migration for RefreshTokenUserIndex {
+    fn version(&self) -> i32 {
+        7
+    }
+
+    fn name(&self) -> &'static str {
+        "refresh_tokens_user_id"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // Password changes and account deletion revoke every session of a user.
+        db.collection::<Document>("refresh_tokens")
+            .create_index(named_index(doc! { "user_id": 1 }, "user_id"))
+            .await?;
+        Ok(())
+    }
+}
+
+struct AccountTokenIndexes;
+
+#[async_trait]
+impl Migration for AccountTokenIndexes {
+    fn version(&self) -> i32 {
+        8
+    }
+
+    fn name(&self) -> &'static str {
+        "account_token_indexes"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        let token_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "token_hash": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name("token_hash_unique".to_string())
+                        .unique(true)
+                        .build(),
+                )
+                .build(),
+            named_index(doc! { "user_id": 1, "purpose": 1 }, "user_id_purpose"),
+            IndexModel::builder()
+                .keys(doc! { "expires_at": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name("expires_at_ttl".to_string())
+                        .expire_after(Duration::ZERO)
+                        .build(),
+                )
+                .build(),
+        ];
+        db.collection::<Document>("account_tokens")
+            .create_indexes(token_indexes)
+            .await?;
+        // Password reset looks accounts up by address.
+        db.collection::<Document>("users")
+            .create_index(named_index(doc! { "email": 1 }, "email"))
+            .await?;
+        Ok(())
+    }
+}
+
+struct ApiKeyIndexes;
+
+#[async_trait]
+impl Migration for ApiKeyIndexes {
+    fn version(&self) -> i32 {
+        9
+    }
+
+    fn name(&self) -> &'static str {
+        "api_key_indexes"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        let indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "key_hash": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name("key_hash_unique".to_string())
+                        .unique(true)
+                        .build(),
+                )
+                .build(),
+            named_index(doc! { "user_id": 1, "_id": -1 }, "user_id_id"),
+        ];
+        db.collection::<Document>("api_keys")
+            .create_indexes(indexes)
+            .await?;
+        Ok(())
+    }
+}
+
+struct ProductOwnerIndex;
+
+#[async_trait]
+impl Migration for ProductOwnerIndex {
+    fn version(&self) -> i32 {
+        10
+    }
+
+    fn name(&self) -> &'static str {
+        "product_owner_index"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        db.collection::<Document>("products")
+            .create_index(named_index(doc! { "owner_id": 1, "_id": 1 }, "owner_id_id"))
+            .await?;
+        Ok(())
+    }
+}
+
+struct BackfillProductVersions;
+
+#[async_trait]
+impl Migration for BackfillProductVersions {
+    fn version(&self) -> i32 {
+        11
+    }
+
+    fn name(&self) -> &'static str {
+        "backfill_product_versions"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // Conditional updates match on the stored version, so it must exist.
+        let result = db
+            .collection::<Document>("products")
+            .update_many(
+                doc! { "version": { "$exists": false } },
+                doc! { "$set": { "version": 1_i64 } },
+            )
+            .await?;
+        info!("Assigned version 1 to {} existing products", result.modified_count);
+        Ok(())
+    }
+}
+
+struct ProductTrashIndex;
+
+#[async_trait]
+impl Migration for ProductTrashIndex {
+    fn version(&self) -> i32 {
+        12
+    }
+
+    fn name(&self) -> &'static str {
+        "product_trash_index"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // Serves the purge job; live products all share the null key.
+        db.collection::<Document>("products")
+            .create_index(named_index(doc! { "deleted_at": 1 }, "deleted_at"))
+            .await?;
+        Ok(())
+    }
+}
+
+struct ProductSkuIndexes;
+
+#[async_trait]
+impl Migration for ProductSkuIndexes {
+    fn version(&self) -> i32 {
+        13
+    }
+
+    fn name(&self) -> &'static str {
+        "product_sku_indexes"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        // Products created before SKUs existed have none and stay out of the
+        // index. Trashed products keep theirs so a restore cannot clash.
+        let sku_index = IndexModel::builder()
+            .keys(doc! { "sku": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("sku_unique".to_string())
+                    .unique(true)
+                    .partial_filter_expression(doc! { "sku": { "$type": "string" } })
+                    .build(),
+            )
+            .build();
+        db.collection::<Document>("products")
+            .create_index(sku_index)
+            .await?;
+        // Finished import reports are kept for a week.
+        let job_ttl = IndexModel::builder()
+            .keys(doc! { "finished_at": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("finished_at_ttl".to_string())
+                    .expire_after(Duration::from_secs(7 * 24 * 60 * 60))
+                    .build(),
+            )
+            .build();
+        db.collection::<Document>("product_import_jobs")
+            .create_index(job_ttl)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Converts legacy f64 prices, which had no currency, to minor units of
+/// `default_currency`.
+struct ProductPricesInMinorUnits {
+    default_currency: Currency,
+}
+
+#[async_trait]
+impl Migration for ProductPricesInMinorUnits {
+    fn version(&self) -> i32 {
+        14
+    }
+
+    fn name(&self) -> &'static str {
+        "product_prices_in_minor_units"
+    }
+
+    async fn up(&self, db: &Database) -> Result<(), MongoError> {
+        let products = db.collection::<Document>("products");
+        // Rounding to the minor unit undoes the binary approximation.
+        let scale = 10_i64.pow(self.default_currency.exponent);
+        let result = products
+            .update_many(
+                doc! {
+                    "price_minor": { "$exists": false },
+                    "price": { "$type": "number" },
+                },
+                vec![
+                    doc! { "$set": {
+                        "price_minor": {
+                            "$toLong": { "$round": [{ "$multiply": ["$price", scale] }, 0] }
+                        },
+                        "currency": { "$ifNull": ["$currency", self.default_currency.code] },
+                    } },
+                    doc! { "$unset": "price" },
+                ],
+            )
+            .await?;
+        info!("Converted {} product prices to minor units", result.modified_count);
+
+        match products.drop_index("price_id").await {
+            Ok(()) => {}
+            // Already dropped by an earlier run.
+            Err(e)
+                if matches!(
+                    e.kind.as_ref(),
+                    ErrorKind::Command(e) if e.code == INDEX_NOT_FOUND_CODE
+                ) => {}
+            Err(e) => return Err(e.into()),
+        }
+        products
+            .create_index(named_index(doc! { "price_minor": 1, "_id": 1 }, "price_minor_id"))
+            .await?;
+        Ok(())
+    }
+}
@@ -1 +1,4 @@
-pub mod mongo;
\ No newline at end of file
+pub mod memory;
+pub mod migrations;
+pub mod mongo;
+pub mod repository;
\ No newline at end of file
@@ -1,10 +1,30 @@
-use crate::{auth::models::User, message::models::Message};
-use crate::products::models::Product;
-use futures::stream::TryStreamExt;
+use crate::{
+    auth::models::{
+        AccountToken, ApiKey, RefreshToken, RevokedToken, Role, TokenPurpose, User,
+    },
+    message::models::Message,
+};
+use crate::config::Config;
+use crate::db::migrations::{MigrationRecord, MigrationRunner};
+use crate::db::repository::{
+    ApiKeyRepository, DeadLetterRepository, ImportJobRepository, MailRepository, MessageRepository,
+    OutboxRepository, ProductRepository, ProductStream, TokenRepository, UserRepository,
+};
+use crate::mail::models::OutgoingEmail;
+use crate::dead_letter::models::DeadLetter;
+use crate::outbox::models::{OutboxEntry, OutboxStatus, ProductOutboxFactory, UserOutboxFactory};
+use crate::products::models::{
+    ImportJob, Product, ProductFilter, ProductQuery, ProductSearchQuery, ProductWrite,
+    ProductWriteOutcome, ScoredProduct, SortOrder,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{StreamExt, TryStreamExt};
 use mongodb::{
-    Client, Collection, Database,
-    bson::{Document, doc, oid::ObjectId},
-    options::ClientOptions,
+    Client, ClientSession, Collection, Database,
+    bson::{self, Document, doc, oid::ObjectId},
+    error::{ErrorKind, WriteFailure},
+    options::{ClientOptions, ReturnDocument},
 };
 use thiserror::Error;
 
@@ -16,10 +36,50 @@ pub enum MongoError {
     NotFound,
     #[error("Duplicate key error: {0}")]
     DuplicateKey(String),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+pub(crate) fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == DUPLICATE_KEY_CODE,
+        ErrorKind::Command(e) => e.code == DUPLICATE_KEY_CODE,
+        ErrorKind::InsertMany(e) => e
+            .write_errors
+            .iter()
+            .flatten()
+            .any(|e| e.code == DUPLICATE_KEY_CODE),
+        _ => false,
+    }
+}
+
+/// Position in an `insert_many` of the document that broke a unique index.
+fn duplicate_key_index(error: &mongodb::error::Error) -> Option<usize> {
+    match error.kind.as_ref() {
+        ErrorKind::InsertMany(e) => e
+            .write_errors
+            .iter()
+            .flatten()
+            .find(|e| e.code == DUPLICATE_KEY_CODE)
+            .map(|e| e.index),
+        _ => None,
+    }
+}
+
+/// Reports a clash on the unique SKU index as `DuplicateKey`; it is the only
+/// unique index on products besides `_id`.
+fn duplicate_sku(error: MongoError) -> MongoError {
+    match error {
+        MongoError::MongoDb(e) if is_duplicate_key(&e) => MongoError::DuplicateKey("sku".to_string()),
+        other => other,
+    }
 }
 
 #[derive(Clone)]
 pub struct MongoRepo {
+    client: Client,
     db: Database,
 }
 
@@ -30,75 +90,1014 @@ impl MongoRepo {
         let client = Client::with_options(client_options)?;
         let db = client.database(db_name);
         println!("MongoDB connected successfully.");
-        Ok(Self { db })
+        Ok(Self { client, db })
+    }
+
+    /// Applies pending schema migrations, returning the ones run now.
+    pub async fn migrate(&self, config: &Config) -> Result<Vec<MigrationRecord>, MongoError> {
+        MigrationRunner::new(self.db.clone(), config).run().await
+    }
+
+    pub async fn applied_migrations(
+        &self,
+        config: &Config,
+    ) -> Result<Vec<MigrationRecord>, MongoError> {
+        MigrationRunner::new(self.db.clone(), config).applied().await
     }
 
     fn users_collection(&self) -> Collection<User> {
         self.db.collection::<User>("users")
     }
 
+    fn refresh_tokens_collection(&self) -> Collection<RefreshToken> {
+        self.db.collection::<RefreshToken>("refresh_tokens")
+    }
+
+    fn revoked_tokens_collection(&self) -> Collection<RevokedToken> {
+        self.db.collection::<RevokedToken>("revoked_tokens")
+    }
+
+    fn account_tokens_collection(&self) -> Collection<AccountToken> {
+        self.db.collection::<AccountToken>("account_tokens")
+    }
+
+    fn api_keys_collection(&self) -> Collection<ApiKey> {
+        self.db.collection::<ApiKey>("api_keys")
+    }
+
+    fn outgoing_emails_collection(&self) -> Collection<OutgoingEmail> {
+        self.db.collection::<OutgoingEmail>("outgoing_emails")
+    }
+
     fn products_collection(&self) -> Collection<Product> {
         self.db.collection::<Product>("products")
     }
 
+    fn import_jobs_collection(&self) -> Collection<ImportJob> {
+        self.db.collection::<ImportJob>("product_import_jobs")
+    }
+
     fn message_collection(&self) -> Collection<Message> {
         self.db.collection::<Message>("messages")
     }
 
-    pub async fn create_user(&self, new_user: User) -> Result<ObjectId, MongoError> {
-        let result = self.users_collection().insert_one(new_user).await?;
-        Ok(result.inserted_id.as_object_id().unwrap())
+    fn outbox_collection(&self) -> Collection<OutboxEntry> {
+        self.db.collection::<OutboxEntry>("outbox")
+    }
+
+    fn dead_letter_collection(&self) -> Collection<DeadLetter> {
+        self.db.collection::<DeadLetter>("dead_letters")
+    }
+
+    async fn start_transaction(&self) -> Result<ClientSession, MongoError> {
+        let mut session = self.client.start_session().await?;
+        session.start_transaction().await?;
+        Ok(session)
+    }
+
+    /// One transaction for `write_products`. `Ok(Err(position))` means the
+    /// write at `position` clashed on the SKU index and nothing was written.
+    async fn try_write_products(
+        &self,
+        writes: &[&ProductWrite],
+    ) -> Result<Result<Vec<Option<Product>>, usize>, MongoError> {
+        // The server-side `bulkWrite` command needs MongoDB 8; on 7 the
+        // inserts and outbox entries go out as one `insert_many` each, and the
+        // conditional updates run in the same transaction.
+        let mut session = self.start_transaction().await?;
+        let mut clash = None;
+        let result = async {
+            let mut outcomes = Vec::with_capacity(writes.len());
+            let mut created = Vec::new();
+            let mut created_positions = Vec::new();
+            let mut entries = Vec::new();
+            for (position, write) in writes.iter().enumerate() {
+                let outcome = match write {
+                    ProductWrite::Create { product, event } => {
+                        created.push(product.clone());
+                        created_positions.push(position);
+                        entries.push(event.clone());
+                        Some(product.clone())
+                    }
+                    ProductWrite::Update {
+                        id,
+                        expected_version,
+                        update,
+                        event,
+                    } => {
+                        let filter =
+                            doc! { "_id": id, "version": expected_version, "deleted_at": null };
+                        let update = doc! { "$set": update.clone(), "$inc": { "version": 1 } };
+                        let updated = self
+                            .products_collection()
+                            .find_one_and_update(filter, update)
+                            .return_document(ReturnDocument::After)
+                            .session(&mut session)
+                            .await
+                            .inspect_err(|e| {
+                                if is_duplicate_key(e) {
+                                    clash = Some(position);
+                                }
+                            })?;
+                        if let Some(updated) = &updated {
+                            entries.push(event(updated)?);
+                        }
+                        updated
+                    }
+                    ProductWrite::Delete {
+                        id,
+                        expected_version,
+                        deleted_by,
+                        event,
+                    } => {
+                        let now = bson::DateTime::now();
+                        let filter =
+                            doc! { "_id": id, "version": expected_version, "deleted_at": null };
+                        let update = doc! {
+                            "$set": { "deleted_at": now, "deleted_by": deleted_by, "updated_at": now },
+                            "$inc": { "version": 1 },
+                        };
+                        let trashed = self
+                            .products_collection()
+                            .find_one_and_update(filter, update)
+                            .return_document(ReturnDocument::After)
+                            .session(&mut session)
+                            .await?;
+                        if trashed.is_some() {
+                            entries.push(event.clone());
+                        }
+                        trashed
+                    }
+                };
+                outcomes.push(outcome);
+            }
+            if !created.is_empty() {
+                self.products_collection()
+                    .insert_many(created)
+                    .session(&mut session)
+                    .await
+                    .inspect_err(|e| {
+                        clash = duplicate_key_index(e).map(|index| created_positions[index]);
+                    })?;
+            }
+            if !entries.is_empty() {
+                self.outbox_collection()
+                    .insert_many(entries)
+                    .session(&mut session)
+                    .await?;
+            }
+            Ok(outcomes)
+        }
+        .await;
+        match (Self::finish_transaction(&mut session, result).await, clash) {
+            (Ok(outcomes), _) => Ok(Ok(outcomes)),
+            (Err(_), Some(position)) => Ok(Err(position)),
+            (Err(e), None) => Err(duplicate_sku(e)),
+        }
+    }
+
+    async fn finish_transaction<T>(
+        session: &mut ClientSession,
+        result: Result<T, MongoError>,
+    ) -> Result<T, MongoError> {
+        match result {
+            Ok(value) => {
+                session.commit_transaction().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                if let Err(abort_err) = session.abort_transaction().await {
+                    tracing::error!("Failed to abort transaction: {:?}", abort_err);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+fn product_filter_document(filter: &ProductFilter) -> Document {
+    let mut document = Document::new();
+
+    let mut price = Document::new();
+    if let Some(min_price) = filter.min_price_minor {
+        price.insert("$gte", min_price);
+    }
+    if let Some(max_price) = filter.max_price_minor {
+        price.insert("$lte", max_price);
+    }
+    if !price.is_empty() {
+        document.insert("price_minor", price);
+    }
+    if let Some(currency) = &filter.currency {
+        document.insert("currency", currency);
+    }
+    if let Some(name) = &filter.name_contains {
+        document.insert(
+            "name",
+            doc! { "$regex": regex::escape(name), "$options": "i" },
+        );
+    }
+    if let Some(created_after) = filter.created_after {
+        document.insert(
+            "created_at",
+            doc! { "$gt": bson::DateTime::from_chrono(created_after) },
+        );
+    }
+    if let Some(owner_id) = filter.owner_id {
+        document.insert("owner_id", owner_id);
     }
+    if filter.trashed {
+        document.insert("deleted_at", doc! { "$ne": null });
+    } else {
+        document.insert("deleted_at", bson::Bson::Null);
+    }
+
+    document
+}
 
-    pub async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MongoError> {
+#[async_trait]
+impl UserRepository for MongoRepo {
+    async fn create_user(&self, new_user: User) -> Result<ObjectId, MongoError> {
+        let username = new_user.username.clone();
+        match self.users_collection().insert_one(new_user).await {
+            Ok(result) => Ok(result.inserted_id.as_object_id().unwrap()),
+            Err(e) if is_duplicate_key(&e) => Err(MongoError::DuplicateKey(username)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MongoError> {
         let filter = doc! { "username": username };
         Ok(self.users_collection().find_one(filter).await?)
     }
 
-    pub async fn create_product(&self, new_product: Product) -> Result<ObjectId, MongoError> {
-        let result = self.products_collection().insert_one(new_product).await?;
-        Ok(result.inserted_id.as_object_id().unwrap())
+    async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, MongoError> {
+        Ok(self.users_collection().find_one(doc! { "_id": id }).await?)
     }
 
-    pub async fn find_product_by_id(&self, id: ObjectId) -> Result<Option<Product>, MongoError> {
-        let filter = doc! { "_id": id };
-        Ok(self.products_collection().find_one(filter).await?)
+    async fn find_users_by_email(&self, email: &str) -> Result<Vec<User>, MongoError> {
+        let cursor = self.users_collection().find(doc! { "email": email }).await?;
+        Ok(cursor.try_collect().await?)
     }
 
-    pub async fn find_all_products(&self) -> Result<Vec<Product>, MongoError> {
-        let cursor = self.products_collection().find(doc! {}).await?;
-        let products: Vec<Product> = cursor.try_collect().await?;
-        Ok(products)
+    async fn mark_email_verified(&self, id: ObjectId, email: &str) -> Result<bool, MongoError> {
+        let result = self
+            .users_collection()
+            .update_one(
+                doc! { "_id": id, "email": email },
+                doc! { "$set": { "email_verified": true } },
+            )
+            .await?;
+        Ok(result.matched_count == 1)
     }
 
-    pub async fn update_product(
+    async fn update_user_profile(
         &self,
         id: ObjectId,
-        update_doc: Document,
+        update: Document,
+        event: UserOutboxFactory,
+    ) -> Result<Option<User>, MongoError> {
+        let mut session = self.start_transaction().await?;
+        let result = async {
+            let Some(updated_user) = self
+                .users_collection()
+                .find_one_and_update(doc! { "_id": id }, doc! { "$set": update })
+                .return_document(ReturnDocument::After)
+                .session(&mut session)
+                .await?
+            else {
+                return Ok(None);
+            };
+            self.outbox_collection()
+                .insert_one(event(&updated_user)?)
+                .session(&mut session)
+                .await?;
+            Ok(Some(updated_user))
+        }
+        .await;
+        Self::finish_transaction(&mut session, result).await
+    }
+
+    async fn update_user_password(
+        &self,
+        id: ObjectId,
+        password_hash: String,
+        event: OutboxEntry,
     ) -> Result<bool, MongoError> {
-        let filter = doc! { "_id": id };
-        let update = doc! { "$set": update_doc };
+        let mut session = self.start_transaction().await?;
+        let result = async {
+            let result = self
+                .users_collection()
+                .update_one(
+                    doc! { "_id": id },
+                    doc! { "$set": { "password_hash": password_hash } },
+                )
+                .session(&mut session)
+                .await?;
+            if result.matched_count == 0 {
+                return Ok(false);
+            }
+            self.outbox_collection()
+                .insert_one(event)
+                .session(&mut session)
+                .await?;
+            Ok(true)
+        }
+        .await;
+        Self::finish_transaction(&mut session, result).await
+    }
+
+    async fn delete_user(&self, id: ObjectId, event: OutboxEntry) -> Result<bool, MongoError> {
+        let mut session = self.start_transaction().await?;
+        let result = async {
+            let result = self
+                .users_collection()
+                .delete_one(doc! { "_id": id })
+                .session(&mut session)
+                .await?;
+            if result.deleted_count == 0 {
+                return Ok(false);
+            }
+            self.outbox_collection()
+                .insert_one(event)
+                .session(&mut session)
+                .await?;
+            Ok(true)
+        }
+        .await;
+        Self::finish_transaction(&mut session, result).await
+    }
+
+    async fn record_failed_login(
+        &self,
+        id: ObjectId,
+        now: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+    ) -> Result<Option<User>, MongoError> {
+        // A pipeline update keeps concurrent failures from losing counts.
+        let update = vec![doc! {
+            "$set": {
+                "failed_login_count": {
+                    "$cond": [
+                        { "$gte": ["$last_failed_login_at", bson::DateTime::from_chrono(window_start)] },
+                        { "$add": [{ "$ifNull": ["$failed_login_count", 0] }, 1] },
+                        1
+                    ]
+                },
+                "last_failed_login_at": bson::DateTime::from_chrono(now),
+            }
+        }];
+        Ok(self
+            .users_collection()
+            .find_one_and_update(doc! { "_id": id }, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn lock_user(&self, id: ObjectId, until: DateTime<Utc>) -> Result<(), MongoError> {
+        self.users_collection()
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "locked_until": bson::DateTime::from_chrono(until) } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn reset_failed_logins(&self, id: ObjectId) -> Result<(), MongoError> {
+        self.users_collection()
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": { "failed_login_count": 0 },
+                    "$unset": { "last_failed_login_at": "", "locked_until": "" },
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn bump_token_epoch(&self, id: ObjectId) -> Result<(), MongoError> {
+        self.users_collection()
+            .update_one(doc! { "_id": id }, doc! { "$inc": { "token_epoch": 1 } })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_user_role(
+        &self,
+        username: &str,
+        role: Role,
+    ) -> Result<Option<User>, MongoError> {
+        Ok(self
+            .users_collection()
+            .find_one_and_update(
+                doc! { "username": username },
+                doc! { "$set": { "role": role.as_str() } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl TokenRepository for MongoRepo {
+    async fn create_refresh_token(&self, token: RefreshToken) -> Result<ObjectId, MongoError> {
+        let result = self.refresh_tokens_collection().insert_one(token).await?;
+        Ok(result.inserted_id.as_object_id().unwrap())
+    }
+
+    async fn find_refresh_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, MongoError> {
+        Ok(self
+            .refresh_tokens_collection()
+            .find_one(doc! { "token_hash": token_hash })
+            .await?)
+    }
+
+    async fn claim_refresh_token(&self, id: ObjectId) -> Result<bool, MongoError> {
+        let result = self
+            .refresh_tokens_collection()
+            .update_one(
+                doc! { "_id": id, "used_at": null, "revoked_at": null },
+                doc! { "$set": { "used_at": bson::DateTime::now() } },
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<u64, MongoError> {
         let result = self
+            .refresh_tokens_collection()
+            .update_many(
+                doc! { "family_id": family_id, "revoked_at": null },
+                doc! { "$set": { "revoked_at": bson::DateTime::now() } },
+            )
+            .await?;
+        Ok(result.modified_count)
+    }
+
+    async fn revoke_user_refresh_tokens(&self, user_id: ObjectId) -> Result<u64, MongoError> {
+        let result = self
+            .refresh_tokens_collection()
+            .update_many(
+                doc! { "user_id": user_id, "revoked_at": null },
+                doc! { "$set": { "revoked_at": bson::DateTime::now() } },
+            )
+            .await?;
+        Ok(result.modified_count)
+    }
+
+    async fn revoke_access_token(&self, token: RevokedToken) -> Result<(), MongoError> {
+        match self.revoked_tokens_collection().insert_one(token).await {
+            Ok(_) => Ok(()),
+            // Revoking the same token twice is a no-op.
+            Err(e) if is_duplicate_key(&e) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn is_access_token_revoked(&self, jti: &str) -> Result<bool, MongoError> {
+        let count = self
+            .revoked_tokens_collection()
+            .count_documents(doc! { "_id": jti })
+            .await?;
+        Ok(count > 0)
+    }
+
+    async fn create_account_token(&self, token: AccountToken) -> Result<ObjectId, MongoError> {
+        let purpose = token.purpose.as_str();
+        self.account_tokens_collection()
+            .delete_many(doc! { "user_id": token.user_id, "purpose": purpose, "used_at": null })
+            .await?;
+        let result = self.account_tokens_collection().insert_one(token).await?;
+        Ok(result.inserted_id.as_object_id().unwrap())
+    }
+
+    async fn claim_account_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>, MongoError> {
+        let now = bson::DateTime::from_chrono(now);
+        Ok(self
+            .account_tokens_collection()
+            .find_one_and_update(
+                doc! {
+                    "token_hash": token_hash,
+                    "purpose": purpose.as_str(),
+                    "used_at": null,
+                    "expires_at": { "$gt": now },
+                },
+                doc! { "$set": { "used_at": now } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for MongoRepo {
+    async fn create_api_key(&self, key: ApiKey) -> Result<ObjectId, MongoError> {
+        let result = self.api_keys_collection().insert_one(key).await?;
+        Ok(result.inserted_id.as_object_id().unwrap())
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, MongoError> {
+        Ok(self
+            .api_keys_collection()
+            .find_one(doc! { "key_hash": key_hash })
+            .await?)
+    }
+
+    async fn list_api_keys(&self, user_id: ObjectId) -> Result<Vec<ApiKey>, MongoError> {
+        let cursor = self
+            .api_keys_collection()
+            .find(doc! { "user_id": user_id })
+            .sort(doc! { "_id": -1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn revoke_api_key(&self, id: ObjectId, user_id: ObjectId) -> Result<bool, MongoError> {
+        let result = self
+            .api_keys_collection()
+            .update_one(
+                doc! { "_id": id, "user_id": user_id, "revoked_at": null },
+                doc! { "$set": { "revoked_at": bson::DateTime::now() } },
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    async fn revoke_user_api_keys(&self, user_id: ObjectId) -> Result<u64, MongoError> {
+        let result = self
+            .api_keys_collection()
+            .update_many(
+                doc! { "user_id": user_id, "revoked_at": null },
+                doc! { "$set": { "revoked_at": bson::DateTime::now() } },
+            )
+            .await?;
+        Ok(result.modified_count)
+    }
+
+    async fn touch_api_key(
+        &self,
+        id: ObjectId,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<(), MongoError> {
+        // Filtering on staleness keeps busy keys from costing a write per call.
+        self.api_keys_collection()
+            .update_one(
+                doc! {
+                    "_id": id,
+                    "$or": [
+                        { "last_used_at": null },
+                        { "last_used_at": { "$lt": bson::DateTime::from_chrono(stale_before) } },
+                    ],
+                },
+                doc! { "$set": { "last_used_at": bson::DateTime::from_chrono(now) } },
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MailRepository for MongoRepo {
+    async fn enqueue_email(&self, email: OutgoingEmail) -> Result<ObjectId, MongoError> {
+        let result = self.outgoing_emails_collection().insert_one(email).await?;
+        Ok(result.inserted_id.as_object_id().unwrap())
+    }
+}
+
+#[async_trait]
+impl ProductRepository for MongoRepo {
+    async fn create_product(
+        &self,
+        new_product: Product,
+        event: OutboxEntry,
+    ) -> Result<ObjectId, MongoError> {
+        let mut session = self.start_transaction().await?;
+        let result = async {
+            let result = self
+                .products_collection()
+                .insert_one(new_product)
+                .session(&mut session)
+                .await?;
+            self.outbox_collection()
+                .insert_one(event)
+                .session(&mut session)
+                .await?;
+            Ok(result.inserted_id.as_object_id().unwrap())
+        }
+        .await;
+        Self::finish_transaction(&mut session, result)
+            .await
+            .map_err(duplicate_sku)
+    }
+
+    async fn find_product_by_id(&self, id: ObjectId) -> Result<Option<Product>, MongoError> {
+        let filter = doc! { "_id": id, "deleted_at": null };
+        Ok(self.products_collection().find_one(filter).await?)
+    }
+
+    async fn find_trashed_product(&self, id: ObjectId) -> Result<Option<Product>, MongoError> {
+        let filter = doc! { "_id": id, "deleted_at": { "$ne": null } };
+        Ok(self.products_collection().find_one(filter).await?)
+    }
+
+    async fn find_products_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Product>, MongoError> {
+        let filter = doc! { "_id": { "$in": ids }, "deleted_at": null };
+        let cursor = self.products_collection().find(filter).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_products_by_skus(&self, skus: &[String]) -> Result<Vec<Product>, MongoError> {
+        let filter = doc! { "sku": { "$in": skus } };
+        let cursor = self.products_collection().find(filter).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_products(&self, query: &ProductQuery) -> Result<Vec<Product>, MongoError> {
+        let field = query.sort.field_name();
+        let (direction, operator) = match query.order {
+            SortOrder::Asc => (1, "$gt"),
+            SortOrder::Desc => (-1, "$lt"),
+        };
+
+        let mut conditions = vec![product_filter_document(&query.filter)];
+        if let Some(after) = &query.after {
+            conditions.push(doc! {
+                "$or": [
+                    { field: { operator: after.value.clone() } },
+                    { field: after.value.clone(), "_id": { operator: after.id } },
+                ]
+            });
+        }
+
+        let cursor = self
             .products_collection()
-            .update_one(filter, update)
+            .find(doc! { "$and": conditions })
+            .sort(doc! { field: direction, "_id": direction })
+            .limit(i64::try_from(query.limit).unwrap_or(i64::MAX))
             .await?;
-        Ok(result.matched_count > 0)
+        Ok(cursor.try_collect().await?)
     }
 
-    pub async fn delete_product(&self, id: ObjectId) -> Result<bool, MongoError> {
-        let filter = doc! { "_id": id };
-        let result = self.products_collection().delete_one(filter).await?;
-        Ok(result.deleted_count > 0)
+    async fn stream_products(&self, filter: &ProductFilter) -> Result<ProductStream, MongoError> {
+        let cursor = self
+            .products_collection()
+            .find(product_filter_document(filter))
+            .sort(doc! { "_id": 1 })
+            .await?;
+        Ok(cursor.map_err(MongoError::from).boxed())
+    }
+
+    async fn search_products(
+        &self,
+        query: &ProductSearchQuery,
+    ) -> Result<Vec<ScoredProduct>, MongoError> {
+        let mut pipeline = vec![
+            doc! { "$match": { "$text": { "$search": &query.text }, "deleted_at": null } },
+            doc! { "$addFields": { "score": { "$meta": "textScore" } } },
+        ];
+        if let Some(after) = &query.after {
+            pipeline.push(doc! {
+                "$match": {
+                    "$or": [
+                        { "score": { "$lt": after.score } },
+                        { "score": after.score, "_id": { "$gt": after.id } },
+                    ]
+                }
+            });
+        }
+        pipeline.push(doc! { "$sort": { "score": -1, "_id": 1 } });
+        pipeline.push(doc! { "$limit": i64::try_from(query.limit).unwrap_or(i64::MAX) });
+
+        let documents: Vec<Document> = self
+            .products_collection()
+            .aggregate(pipeline)
+            .await?
+            .try_collect()
+            .await?;
+        documents
+            .into_iter()
+            .map(|mut document| {
+                let score = document
+                    .remove("score")
+                    .and_then(|s| s.as_f64())
+                    .unwrap_or(0.0);
+                let product: Product =
+                    bson::from_document(document).map_err(mongodb::error::Error::from)?;
+                Ok(ScoredProduct { product, score })
+            })
+            .collect()
     }
 
-    pub async fn find_all_message(&self) -> Result<Vec<Message>, MongoError> {
+    async fn update_product(
+        &self,
+        id: ObjectId,
+        expected_version: i64,
+        update_doc: Document,
+        event: ProductOutboxFactory,
+    ) -> Result<Option<Product>, MongoError> {
+        let mut session = self.start_transaction().await?;
+        let result = async {
+            let filter = doc! { "_id": id, "version": expected_version, "deleted_at": null };
+            let update = doc! { "$set": update_doc, "$inc": { "version": 1 } };
+            let Some(updated_product) = self
+                .products_collection()
+                .find_one_and_update(filter, update)
+                .return_document(ReturnDocument::After)
+                .session(&mut session)
+                .await?
+            else {
+                return Ok(None);
+            };
+            self.outbox_collection()
+                .insert_one(event(&updated_product)?)
+                .session(&mut session)
+                .await?;
+            Ok(Some(updated_product))
+        }
+        .await;
+        Self::finish_transaction(&mut session, result)
+            .await
+            .map_err(duplicate_sku)
+    }
+
+    async fn delete_product(
+        &self,
+        id: ObjectId,
+        deleted_by: ObjectId,
+        event: OutboxEntry,
+    ) -> Result<bool, MongoError> {
+        let mut session = self.start_transaction().await?;
+        let result = async {
+            let now = bson::DateTime::now();
+            let filter = doc! { "_id": id, "deleted_at": null };
+            let update = doc! {
+                "$set": { "deleted_at": now, "deleted_by": deleted_by, "updated_at": now },
+                "$inc": { "version": 1 },
+            };
+            let result = self
+                .products_collection()
+                .update_one(filter, update)
+                .session(&mut session)
+                .await?;
+            if result.modified_count == 0 {
+                return Ok(false);
+            }
+            self.outbox_collection()
+                .insert_one(event)
+                .session(&mut session)
+                .await?;
+            Ok(true)
+        }
+        .await;
+        Self::finish_transaction(&mut session, result).await
+    }
+
+    async fn restore_product(
+        &self,
+        id: ObjectId,
+        restored_by: ObjectId,
+        event: ProductOutboxFactory,
+    ) -> Result<Option<Product>, MongoError> {
+        let mut session = self.start_transaction().await?;
+        let result = async {
+            let filter = doc! { "_id": id, "deleted_at": { "$ne": null } };
+            let update = doc! {
+                "$unset": { "deleted_at": "", "deleted_by": "" },
+                "$set": { "updated_at": bson::DateTime::now(), "updated_by": restored_by },
+                "$inc": { "version": 1 },
+            };
+            let Some(restored) = self
+                .products_collection()
+                .find_one_and_update(filter, update)
+                .return_document(ReturnDocument::After)
+                .session(&mut session)
+                .await?
+            else {
+                return Ok(None);
+            };
+            self.outbox_collection()
+                .insert_one(event(&restored)?)
+                .session(&mut session)
+                .await?;
+            Ok(Some(restored))
+        }
+        .await;
+        Self::finish_transaction(&mut session, result).await
+    }
+
+    async fn write_products(
+        &self,
+        writes: Vec<ProductWrite>,
+    ) -> Result<Vec<ProductWriteOutcome>, MongoError> {
+        // A SKU taken since the caller checked it aborts the transaction; the
+        // clashing write is then set aside and the rest tried again.
+        let mut outcomes: Vec<Option<ProductWriteOutcome>> =
+            writes.iter().map(|_| None).collect();
+        let mut remaining: Vec<usize> = (0..writes.len()).collect();
+        while !remaining.is_empty() {
+            let attempt: Vec<&ProductWrite> =
+                remaining.iter().map(|&index| &writes[index]).collect();
+            match self.try_write_products(&attempt).await? {
+                Ok(written) => {
+                    for (index, product) in remaining.drain(..).zip(written) {
+                        outcomes[index] = Some(match product {
+                            Some(product) => ProductWriteOutcome::Written(Box::new(product)),
+                            None => ProductWriteOutcome::Stale,
+                        });
+                    }
+                }
+                Err(position) => {
+                    let index = remaining.remove(position);
+                    outcomes[index] = Some(ProductWriteOutcome::SkuTaken);
+                }
+            }
+        }
+        Ok(outcomes
+            .into_iter()
+            .map(|outcome| outcome.expect("every write gets an outcome"))
+            .collect())
+    }
+
+    async fn purge_trashed_products(
+        &self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<u64, MongoError> {
+        let filter = doc! { "deleted_at": { "$lt": bson::DateTime::from_chrono(deleted_before) } };
+        let result = self.products_collection().delete_many(filter).await?;
+        Ok(result.deleted_count)
+    }
+}
+
+#[async_trait]
+impl ImportJobRepository for MongoRepo {
+    async fn create_import_job(&self, job: ImportJob) -> Result<ObjectId, MongoError> {
+        let result = self.import_jobs_collection().insert_one(job).await?;
+        Ok(result.inserted_id.as_object_id().unwrap())
+    }
+
+    async fn find_import_job(&self, id: ObjectId) -> Result<Option<ImportJob>, MongoError> {
+        Ok(self.import_jobs_collection().find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn save_import_job(&self, job: &ImportJob) -> Result<(), MongoError> {
+        let id = job._id.ok_or(MongoError::NotFound)?;
+        self.import_jobs_collection()
+            .replace_one(doc! { "_id": id }, job)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageRepository for MongoRepo {
+    async fn find_all_message(&self) -> Result<Vec<Message>, MongoError> {
         let cursor = self.message_collection().find(doc! {}).await?;
         let messages: Vec<Message> = cursor.try_collect().await?;
         Ok(messages)
     }
 
-    pub async fn create_message(&self, new_message: Message) -> Result<ObjectId, MongoError> {
+    async fn create_message(&self, new_message: Message) -> Result<ObjectId, MongoError> {
         let result = self.message_collection().insert_one(new_message).await?;
         Ok(result.inserted_id.as_object_id().unwrap())
     }
+
+    async fn create_message_if_absent(&self, new_message: Message) -> Result<bool, MongoError> {
+        let Some(event_id) = new_message.event_id.clone() else {
+            self.create_message(new_message).await?;
+            return Ok(true);
+        };
+        let document = bson::to_document(&new_message).map_err(mongodb::error::Error::from)?;
+        let result = self
+            .message_collection()
+            .update_one(
+                doc! { "event_id": event_id },
+                doc! { "$setOnInsert": document },
+            )
+            .upsert(true)
+            .await;
+        match result {
+            Ok(result) => Ok(result.upserted_id.is_some()),
+            // A concurrent upsert for the same event won the unique index.
+            Err(e) if is_duplicate_key(&e) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for MongoRepo {
+    async fn find_due_outbox_entries(&self, limit: i64) -> Result<Vec<OutboxEntry>, MongoError> {
+        let now = bson::DateTime::now();
+        // Only a key's oldest entry is ever attempted while it has a failed
+        // one, so a pending entry waiting for its retry holds back its key.
+        let waiting_keys = self
+            .outbox_collection()
+            .distinct(
+                "key",
+                doc! {
+                    "status": OutboxStatus::Pending.as_str(),
+                    "next_attempt_at": { "$gt": now },
+                },
+            )
+            .await?;
+        let filter = doc! {
+            "status": OutboxStatus::Pending.as_str(),
+            "next_attempt_at": { "$lte": now },
+            "key": { "$nin": waiting_keys },
+        };
+        let cursor = self
+            .outbox_collection()
+            .find(filter)
+            // Entries written together share a timestamp; `_id` keeps them in
+            // write order, which is what per-key ordering depends on.
+            .sort(doc! { "created_at": 1, "_id": 1 })
+            .limit(limit)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn mark_outbox_delivered(&self, id: ObjectId) -> Result<(), MongoError> {
+        let update = doc! {
+            "$set": {
+                "status": OutboxStatus::Delivered.as_str(),
+                "delivered_at": bson::DateTime::now(),
+                "last_error": bson::Bson::Null,
+            },
+            "$inc": { "attempts": 1 },
+        };
+        self.outbox_collection()
+            .update_one(doc! { "_id": id }, update)
+            .await?;
+        Ok(())
+    }
+
+    async fn mark_outbox_failed(
+        &self,
+        id: ObjectId,
+        error: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), MongoError> {
+        let update = doc! {
+            "$set": {
+                "last_error": error,
+                "next_attempt_at": bson::DateTime::from_chrono(next_attempt_at),
+            },
+            "$inc": { "attempts": 1 },
+        };
+        self.outbox_collection()
+            .update_one(doc! { "_id": id }, update)
+            .await?;
+        Ok(())
+    }
+
+    async fn count_pending_outbox(&self) -> Result<u64, MongoError> {
+        let filter = doc! {
+            "status": OutboxStatus::Pending.as_str(),
+        };
+        Ok(self.outbox_collection().count_documents(filter).await?)
+    }
+}
+
+#[async_trait]
+impl DeadLetterRepository for MongoRepo {
+    async fn create_dead_letter(&self, dead_letter: DeadLetter) -> Result<ObjectId, MongoError> {
+        let result = self.dead_letter_collection().insert_one(dead_letter).await?;
+        Ok(result.inserted_id.as_object_id().unwrap())
+    }
+
+    async fn find_all_dead_letters(&self) -> Result<Vec<DeadLetter>, MongoError> {
+        let cursor = self
+            .dead_letter_collection()
+            .find(doc! {})
+            .sort(doc! { "failed_at": -1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_dead_letter_by_id(&self, id: ObjectId) -> Result<Option<DeadLetter>, MongoError> {
+        Ok(self.dead_letter_collection().find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn mark_dead_letter_redriven(&self, id: ObjectId) -> Result<bool, MongoError> {
+        let update = doc! {
+            "$set": { "redriven_at": bson::DateTime::now() },
+            "$inc": { "redrive_count": 1 },
+        };
+        let result = self
+            .dead_letter_collection()
+            .update_one(doc! { "_id": id }, update)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
 }
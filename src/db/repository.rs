@@ -0,0 +1,253 @@
+use crate::{
+    auth::models::{ApiKey, AccountToken, RefreshToken, RevokedToken, Role, TokenPurpose, User},
+    db::mongo::MongoError,
+    dead_letter::models::DeadLetter,
+    mail::models::OutgoingEmail,
+    message::models::Message,
+    outbox::models::{OutboxEntry, ProductOutboxFactory, UserOutboxFactory},
+    products::models::{
+        ImportJob, Product, ProductFilter, ProductQuery, ProductSearchQuery, ProductWrite,
+        ProductWriteOutcome, ScoredProduct,
+    },
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
+use mongodb::bson::{Document, oid::ObjectId};
+
+/// Products read one at a time from the backend, for responses too large to
+/// hold in memory.
+pub type ProductStream = BoxStream<'static, Result<Product, MongoError>>;
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn create_user(&self, new_user: User) -> Result<ObjectId, MongoError>;
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MongoError>;
+    async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, MongoError>;
+    async fn find_users_by_email(&self, email: &str) -> Result<Vec<User>, MongoError>;
+    /// Marks the address verified if it is still the user's current one.
+    async fn mark_email_verified(&self, id: ObjectId, email: &str) -> Result<bool, MongoError>;
+    /// Applies `update` (a `$set` document) and records the event built from
+    /// the updated user in the same transaction.
+    async fn update_user_profile(
+        &self,
+        id: ObjectId,
+        update: Document,
+        event: UserOutboxFactory,
+    ) -> Result<Option<User>, MongoError>;
+    async fn update_user_password(
+        &self,
+        id: ObjectId,
+        password_hash: String,
+        event: OutboxEntry,
+    ) -> Result<bool, MongoError>;
+    async fn delete_user(&self, id: ObjectId, event: OutboxEntry) -> Result<bool, MongoError>;
+    /// Counts a failed login, restarting the count when the previous failure
+    /// is older than `window_start`. Returns the updated user.
+    async fn record_failed_login(
+        &self,
+        id: ObjectId,
+        now: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+    ) -> Result<Option<User>, MongoError>;
+    async fn lock_user(&self, id: ObjectId, until: DateTime<Utc>) -> Result<(), MongoError>;
+    /// Clears failure counters and any lockout.
+    async fn reset_failed_logins(&self, id: ObjectId) -> Result<(), MongoError>;
+    /// Moves the user to a new token epoch, so access tokens issued so far
+    /// are refused.
+    async fn bump_token_epoch(&self, id: ObjectId) -> Result<(), MongoError>;
+    /// Returns the updated user, or `None` if no user has that username.
+    async fn update_user_role(
+        &self,
+        username: &str,
+        role: Role,
+    ) -> Result<Option<User>, MongoError>;
+}
+
+#[async_trait]
+pub trait TokenRepository: Send + Sync {
+    async fn create_refresh_token(&self, token: RefreshToken) -> Result<ObjectId, MongoError>;
+    async fn find_refresh_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, MongoError>;
+    /// Marks the token used if it is still live. Returns `false` when it was
+    /// already used or revoked, which means it is being replayed.
+    async fn claim_refresh_token(&self, id: ObjectId) -> Result<bool, MongoError>;
+    /// Revokes every live token in the family, returning how many were revoked.
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<u64, MongoError>;
+    /// Revokes every live refresh token of the user, signing out all sessions.
+    async fn revoke_user_refresh_tokens(&self, user_id: ObjectId) -> Result<u64, MongoError>;
+    async fn revoke_access_token(&self, token: RevokedToken) -> Result<(), MongoError>;
+    async fn is_access_token_revoked(&self, jti: &str) -> Result<bool, MongoError>;
+    /// Stores `token`, discarding any unused token the user has for the same
+    /// purpose so only the latest email works.
+    async fn create_account_token(&self, token: AccountToken) -> Result<ObjectId, MongoError>;
+    /// Atomically marks a live token of `purpose` used and returns it; `None`
+    /// if it is unknown, expired or already used.
+    async fn claim_account_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>, MongoError>;
+}
+
+#[async_trait]
+pub trait ApiKeyRepository: Send + Sync {
+    async fn create_api_key(&self, key: ApiKey) -> Result<ObjectId, MongoError>;
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, MongoError>;
+    /// The user's keys, newest first, including revoked and expired ones.
+    async fn list_api_keys(&self, user_id: ObjectId) -> Result<Vec<ApiKey>, MongoError>;
+    /// Revokes one of the user's keys. `false` if it is not theirs or is
+    /// already revoked.
+    async fn revoke_api_key(&self, id: ObjectId, user_id: ObjectId) -> Result<bool, MongoError>;
+    /// Revokes every live key of the user, returning how many were revoked.
+    async fn revoke_user_api_keys(&self, user_id: ObjectId) -> Result<u64, MongoError>;
+    /// Records a use, skipping the write when the last one recorded is newer
+    /// than `stale_before`.
+    async fn touch_api_key(
+        &self,
+        id: ObjectId,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<(), MongoError>;
+}
+
+#[async_trait]
+pub trait MailRepository: Send + Sync {
+    async fn enqueue_email(&self, email: OutgoingEmail) -> Result<ObjectId, MongoError>;
+}
+
+/// Product writes store their outbox entry atomically with the change.
+#[async_trait]
+pub trait ProductRepository: Send + Sync {
+    async fn create_product(
+        &self,
+        new_product: Product,
+        event: OutboxEntry,
+    ) -> Result<ObjectId, MongoError>;
+    /// Live products only; trashed ones read as missing.
+    async fn find_product_by_id(&self, id: ObjectId) -> Result<Option<Product>, MongoError>;
+    async fn find_trashed_product(&self, id: ObjectId) -> Result<Option<Product>, MongoError>;
+    /// The live products among `ids`, in no particular order.
+    async fn find_products_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Product>, MongoError>;
+    /// Products holding any of `skus`, trashed ones included since they keep
+    /// their SKU.
+    async fn find_products_by_skus(&self, skus: &[String]) -> Result<Vec<Product>, MongoError>;
+    /// Up to `query.limit` products matching the filter, in sort order,
+    /// starting after `query.after` when set.
+    async fn find_products(&self, query: &ProductQuery) -> Result<Vec<Product>, MongoError>;
+    /// Every product matching `filter`, in `_id` order.
+    async fn stream_products(&self, filter: &ProductFilter) -> Result<ProductStream, MongoError>;
+    /// Full-text search over name and description, best matches first.
+    async fn search_products(
+        &self,
+        query: &ProductSearchQuery,
+    ) -> Result<Vec<ScoredProduct>, MongoError>;
+    /// Applies `update_doc` (a `$set` document) and bumps the version, but
+    /// only if the product is live and still at `expected_version`. Returns
+    /// the updated product, or `None` if it is gone or has moved on.
+    async fn update_product(
+        &self,
+        id: ObjectId,
+        expected_version: i64,
+        update_doc: Document,
+        event: ProductOutboxFactory,
+    ) -> Result<Option<Product>, MongoError>;
+    /// Moves a live product to the trash.
+    async fn delete_product(
+        &self,
+        id: ObjectId,
+        deleted_by: ObjectId,
+        event: OutboxEntry,
+    ) -> Result<bool, MongoError>;
+    /// Takes a product out of the trash, returning it as restored.
+    async fn restore_product(
+        &self,
+        id: ObjectId,
+        restored_by: ObjectId,
+        event: ProductOutboxFactory,
+    ) -> Result<Option<Product>, MongoError>;
+    /// Applies a batch of writes and their events in one transaction, with
+    /// one outcome per write. A write whose SKU was taken meanwhile is left
+    /// out and the others applied without it.
+    async fn write_products(
+        &self,
+        writes: Vec<ProductWrite>,
+    ) -> Result<Vec<ProductWriteOutcome>, MongoError>;
+    /// Permanently removes products trashed before `deleted_before`.
+    async fn purge_trashed_products(
+        &self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<u64, MongoError>;
+}
+
+#[async_trait]
+pub trait ImportJobRepository: Send + Sync {
+    async fn create_import_job(&self, job: ImportJob) -> Result<ObjectId, MongoError>;
+    async fn find_import_job(&self, id: ObjectId) -> Result<Option<ImportJob>, MongoError>;
+    /// Replaces the stored job with `job`, which must have an id.
+    async fn save_import_job(&self, job: &ImportJob) -> Result<(), MongoError>;
+}
+
+#[async_trait]
+pub trait MessageRepository: Send + Sync {
+    async fn find_all_message(&self) -> Result<Vec<Message>, MongoError>;
+    async fn create_message(&self, new_message: Message) -> Result<ObjectId, MongoError>;
+    /// Inserts the message unless one with the same `event_id` already exists.
+    /// Returns whether a new document was written.
+    async fn create_message_if_absent(&self, new_message: Message) -> Result<bool, MongoError>;
+}
+
+#[async_trait]
+pub trait OutboxRepository: Send + Sync {
+    /// Pending entries whose next attempt is due, oldest first. Keys with an
+    /// entry waiting for a retry are left out entirely, so nothing overtakes it.
+    async fn find_due_outbox_entries(&self, limit: i64) -> Result<Vec<OutboxEntry>, MongoError>;
+    async fn mark_outbox_delivered(&self, id: ObjectId) -> Result<(), MongoError>;
+    async fn mark_outbox_failed(
+        &self,
+        id: ObjectId,
+        error: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), MongoError>;
+    async fn count_pending_outbox(&self) -> Result<u64, MongoError>;
+}
+
+#[async_trait]
+pub trait DeadLetterRepository: Send + Sync {
+    async fn create_dead_letter(&self, dead_letter: DeadLetter) -> Result<ObjectId, MongoError>;
+    /// Most recent failures first.
+    async fn find_all_dead_letters(&self) -> Result<Vec<DeadLetter>, MongoError>;
+    async fn find_dead_letter_by_id(&self, id: ObjectId) -> Result<Option<DeadLetter>, MongoError>;
+    async fn mark_dead_letter_redriven(&self, id: ObjectId) -> Result<bool, MongoError>;
+}
+
+/// Everything the API needs from a storage backend. Implemented automatically
+/// for any type that implements the individual repositories.
+pub trait Repository:
+    UserRepository
+    + TokenRepository
+    + ApiKeyRepository
+    + MailRepository
+    + ProductRepository
+    + ImportJobRepository
+    + MessageRepository
+    + OutboxRepository
+    + DeadLetterRepository
+{
+}
+
+impl<T> Repository for T where
+    T: UserRepository
+        + TokenRepository
+        + ApiKeyRepository
+        + MailRepository
+        + ProductRepository
+        + ImportJobRepository
+        + MessageRepository
+        + OutboxRepository
+        + DeadLetterRepository
+{
+}
@@ -0,0 +1,83 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::IntoResponse,
+  Json,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+  dead_letter::models::DeadLetterResponse,
+  error::{AppError, ProblemDetails, parse_object_id},
+  state::AppState,
+};
+
+#[utoipa::path(
+  get,
+  path = "",
+  tag = "dead-letter",
+  responses(
+      (status = 200, description = "List dead-lettered messages successfully", body = [DeadLetterResponse])
+  ),
+  security(
+      ("token" = ["admin"]),
+      ("api_key" = ["admin"])
+  )
+)]
+pub async fn list_dead_letters(
+  State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+  let dead_letters = state.db_repo.find_all_dead_letters().await?;
+  let response: Vec<DeadLetterResponse> = dead_letters
+      .iter()
+      .map(DeadLetterResponse::from_dead_letter)
+      .collect();
+  Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/redrive",
+  tag = "dead-letter",
+  responses(
+      (status = 202, description = "Message re-published to its source topic", body = DeadLetterResponse),
+      (status = 404, description = "Dead letter not found", body = ProblemDetails, content_type = "application/problem+json"),
+      (status = 502, description = "Source topic rejected the message", body = ProblemDetails, content_type = "application/problem+json")
+  ),
+  params(
+      ("id" = String, Path, description = "dead letter id")
+  ),
+  security(
+      ("token" = ["admin"]),
+      ("api_key" = ["admin"])
+  )
+)]
+pub async fn redrive_dead_letter(
+  State(state): State<AppState>,
+  Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+  let object_id = parse_object_id(&id, "dead letter")?;
+
+  let Some(dead_letter) = state.db_repo.find_dead_letter_by_id(object_id).await? else {
+      warn!("Dead letter not found: {}", id);
+      return Err(AppError::NotFound("Dead letter not found".to_string()));
+  };
+
+  state.event_publisher.publish(dead_letter.to_source_record()).await?;
+
+  if let Err(e) = state.db_repo.mark_dead_letter_redriven(object_id).await {
+      error!("Failed to mark dead letter {} as re-driven: {:?}", id, e);
+  }
+  info!("Dead letter {} re-driven to '{}'", id, dead_letter.source_topic);
+
+  match state.db_repo.find_dead_letter_by_id(object_id).await {
+      Ok(Some(updated)) => Ok((
+          StatusCode::ACCEPTED,
+          Json(DeadLetterResponse::from_dead_letter(&updated)),
+      )),
+      _ => Ok((
+          StatusCode::ACCEPTED,
+          Json(DeadLetterResponse::from_dead_letter(&dead_letter)),
+      )),
+  }
+}
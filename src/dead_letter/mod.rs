@@ -0,0 +1,2 @@
+pub mod models;
+pub mod handlers;
\ No newline at end of file
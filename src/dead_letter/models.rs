@@ -0,0 +1,85 @@
+use bson::{Binary, oid::ObjectId, spec::BinarySubtype};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::kafka::envelope::EventHeader;
+use crate::kafka::publisher::EventRecord;
+
+/// A consumed message that could not be processed, kept so it can be
+/// inspected and re-driven to its source topic.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeadLetter {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub source_topic: String,
+    pub source_partition: i32,
+    pub source_offset: i64,
+    pub key: Option<String>,
+    pub payload: Binary,
+    #[serde(default)]
+    pub headers: Vec<EventHeader>,
+    pub error: String,
+    pub handler: Option<String>,
+    pub attempts: u32,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub failed_at: DateTime<Utc>,
+    #[serde(default)]
+    pub redrive_count: u32,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub redriven_at: Option<DateTime<Utc>>,
+}
+
+impl DeadLetter {
+    pub fn payload_binary(bytes: &[u8]) -> Binary {
+        Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    /// The original record, ready to be published back to its source topic.
+    pub fn to_source_record(&self) -> EventRecord {
+        EventRecord {
+            topic: self.source_topic.clone(),
+            key: self.key.clone().unwrap_or_default(),
+            payload: self.payload.bytes.clone(),
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DeadLetterResponse {
+    pub id: String,
+    pub source_topic: String,
+    pub source_partition: i32,
+    pub source_offset: i64,
+    pub key: Option<String>,
+    pub payload: String,
+    pub error: String,
+    pub handler: Option<String>,
+    pub attempts: u32,
+    pub failed_at: String,
+    pub redrive_count: u32,
+    pub redriven_at: Option<String>,
+}
+
+impl DeadLetterResponse {
+    pub fn from_dead_letter(dead_letter: &DeadLetter) -> Self {
+        DeadLetterResponse {
+            id: dead_letter._id.expect("Dead letter from DB must have an ID").to_hex(),
+            source_topic: dead_letter.source_topic.clone(),
+            source_partition: dead_letter.source_partition,
+            source_offset: dead_letter.source_offset,
+            key: dead_letter.key.clone(),
+            payload: String::from_utf8_lossy(&dead_letter.payload.bytes).into_owned(),
+            error: dead_letter.error.clone(),
+            handler: dead_letter.handler.clone(),
+            attempts: dead_letter.attempts,
+            failed_at: dead_letter.failed_at.to_string(),
+            redrive_count: dead_letter.redrive_count,
+            redriven_at: dead_letter.redriven_at.map(|at| at.to_string()),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use axum::{
+    Json,
+    body::{Body, to_bytes},
+    extract::{Request, rejection::JsonRejection},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bcrypt::BcryptError;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tower_http::request_id::RequestId;
+use tracing::error;
+use utoipa::ToSchema;
+use validator::ValidationErrors;
+
+use crate::{
+    auth::utils::JWTError,
+    db::mongo::MongoError,
+    kafka::producer::KafkaError,
+    mail::mailer::MailerError,
+    validation::{FieldError, field_errors},
+};
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Largest plain-text error body the problem middleware will read back when
+/// wrapping a response that did not come from `AppError`.
+const MAX_FOREIGN_ERROR_BODY: usize = 16 * 1024;
+
+/// Machine-readable error identifiers. These are part of the public API:
+/// clients may match on them, so existing values must never change meaning.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    ValidationFailed,
+    Unauthorized,
+    InvalidToken,
+    TokenExpired,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PreconditionFailed,
+    UnsupportedMediaType,
+    TooManyRequests,
+    DatabaseError,
+    EventPublishFailed,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::InvalidToken => "invalid_token",
+            ErrorCode::TokenExpired => "token_expired",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::MethodNotAllowed => "method_not_allowed",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::PreconditionFailed => "precondition_failed",
+            ErrorCode::UnsupportedMediaType => "unsupported_media_type",
+            ErrorCode::TooManyRequests => "too_many_requests",
+            ErrorCode::DatabaseError => "database_error",
+            ErrorCode::EventPublishFailed => "event_publish_failed",
+            ErrorCode::InternalError => "internal_error",
+        }
+    }
+
+    /// Best guess for responses produced outside `AppError`, such as axum's
+    /// own extractor rejections.
+    fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => ErrorCode::MethodNotAllowed,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::PRECONDITION_FAILED => ErrorCode::PreconditionFailed,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+            StatusCode::UNPROCESSABLE_ENTITY => ErrorCode::ValidationFailed,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::TooManyRequests,
+            status if status.is_client_error() => ErrorCode::BadRequest,
+            _ => ErrorCode::InternalError,
+        }
+    }
+}
+
+/// RFC 7807 problem details, served as `application/problem+json`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ProblemDetails {
+    /// URI reference identifying the problem type
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Short summary of the problem type
+    pub title: String,
+    pub status: u16,
+    /// Explanation specific to this occurrence
+    pub detail: String,
+    /// Request path that produced the problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub code: ErrorCode,
+    /// Echoes the `x-request-id` response header, for correlating with logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Per-field failures when the request body broke validation rules
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, code: ErrorCode, detail: impl Into<String>) -> Self {
+        Self {
+            problem_type: format!("/problems/{}", code.as_str().replace('_', "-")),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            instance: None,
+            code,
+            request_id: None,
+            errors: Vec::new(),
+        }
+    }
+
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let mut response = (status, Json(&self)).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(PROBLEM_CONTENT_TYPE),
+        );
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error(transparent)]
+    InvalidBody(#[from] JsonRejection),
+    #[error("Request validation failed")]
+    InvalidFields(#[from] ValidationErrors),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    /// An `If-Match` (or similar) precondition did not hold.
+    #[error("{0}")]
+    PreconditionFailed(String),
+    /// Sent with a `Retry-After` header of `retry_after` seconds.
+    #[error("{detail}")]
+    TooManyRequests { detail: String, retry_after: u64 },
+    #[error(transparent)]
+    Database(#[from] MongoError),
+    #[error(transparent)]
+    Event(#[from] KafkaError),
+    #[error(transparent)]
+    Jwt(#[from] JWTError),
+    #[error(transparent)]
+    Mail(#[from] MailerError),
+    #[error(transparent)]
+    Password(#[from] BcryptError),
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidBody(rejection) => rejection.status(),
+            AppError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Database(MongoError::NotFound) => StatusCode::NOT_FOUND,
+            AppError::Database(MongoError::DuplicateKey(_)) => StatusCode::CONFLICT,
+            AppError::Event(_) => StatusCode::BAD_GATEWAY,
+            AppError::Jwt(JWTError::CreationFailed(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Jwt(_) => StatusCode::UNAUTHORIZED,
+            AppError::Database(_)
+            | AppError::Mail(_)
+            | AppError::Password(_)
+            | AppError::Serialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::BadRequest(_) => ErrorCode::BadRequest,
+            AppError::Validation(_) => ErrorCode::ValidationFailed,
+            AppError::InvalidBody(rejection) => ErrorCode::from_status(rejection.status()),
+            AppError::InvalidFields(_) => ErrorCode::ValidationFailed,
+            AppError::Unauthorized(_) => ErrorCode::Unauthorized,
+            AppError::Forbidden(_) => ErrorCode::Forbidden,
+            AppError::NotFound(_) => ErrorCode::NotFound,
+            AppError::Conflict(_) => ErrorCode::Conflict,
+            AppError::PreconditionFailed(_) => ErrorCode::PreconditionFailed,
+            AppError::TooManyRequests { .. } => ErrorCode::TooManyRequests,
+            AppError::Database(MongoError::NotFound) => ErrorCode::NotFound,
+            AppError::Database(MongoError::DuplicateKey(_)) => ErrorCode::Conflict,
+            AppError::Database(_) => ErrorCode::DatabaseError,
+            AppError::Event(_) => ErrorCode::EventPublishFailed,
+            AppError::Jwt(JWTError::Expired) => ErrorCode::TokenExpired,
+            AppError::Jwt(JWTError::CreationFailed(_)) => ErrorCode::InternalError,
+            AppError::Jwt(_) => ErrorCode::InvalidToken,
+            AppError::Mail(_) | AppError::Password(_) | AppError::Serialization(_) => {
+                ErrorCode::InternalError
+            }
+        }
+    }
+
+    /// Message safe to show clients. Infrastructure errors are described
+    /// generically; their details only go to the logs.
+    fn detail(&self) -> String {
+        match self {
+            AppError::Database(MongoError::NotFound) => "Resource not found".to_string(),
+            AppError::Database(MongoError::DuplicateKey(_)) => "Resource already exists".to_string(),
+            AppError::Database(_) => "A database error occurred".to_string(),
+            AppError::Event(_) => "Failed to publish event".to_string(),
+            AppError::Mail(_) => "Failed to send email".to_string(),
+            AppError::Jwt(JWTError::Expired) => "Token has expired".to_string(),
+            AppError::Jwt(JWTError::Revoked) => "Token has been revoked".to_string(),
+            AppError::Jwt(JWTError::CreationFailed(_)) => "Failed to issue token".to_string(),
+            AppError::Jwt(_) => "Invalid token".to_string(),
+            AppError::Password(_) | AppError::Serialization(_) => {
+                "An internal error occurred".to_string()
+            }
+            AppError::InvalidBody(rejection) => rejection.body_text(),
+            other => other.to_string(),
+        }
+    }
+
+    pub fn to_problem(&self) -> ProblemDetails {
+        let mut problem = ProblemDetails::new(self.status(), self.code(), self.detail());
+        if let AppError::InvalidFields(errors) = self {
+            problem.errors = field_errors(errors);
+        }
+        problem
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if self.status().is_server_error() {
+            error!("Request failed: {:?}", self);
+        }
+        let mut response = self.to_problem().into_response();
+        if let AppError::TooManyRequests { retry_after, .. } = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
+
+/// Parses a path segment as an ObjectId, naming the resource in the error.
+pub fn parse_object_id(id: &str, resource: &str) -> Result<ObjectId, AppError> {
+    id.parse()
+        .map_err(|_| AppError::BadRequest(format!("Invalid {} ID format", resource)))
+}
+
+/// Turns every error response into problem details carrying the request ID
+/// and path. Responses built from `AppError` are enriched in place; anything
+/// else (extractor rejections, unmatched routes) is wrapped, keeping its
+/// status and using its body as the detail.
+pub async fn problem_details_middleware(req: Request, next: Next) -> Response {
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .map(str::to_string);
+    let instance = req.uri().path().to_string();
+
+    let response = next.run(req).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut problem = match parts.extensions.remove::<ProblemDetails>() {
+        Some(problem) => problem,
+        None => {
+            let bytes = to_bytes(body, MAX_FOREIGN_ERROR_BODY)
+                .await
+                .unwrap_or_default();
+            let detail = String::from_utf8_lossy(&bytes).trim().to_string();
+            let detail = if detail.is_empty() {
+                status.canonical_reason().unwrap_or("Error").to_string()
+            } else {
+                detail
+            };
+            ProblemDetails::new(status, ErrorCode::from_status(status), detail)
+        }
+    };
+    problem.instance = Some(instance);
+    problem.request_id = request_id;
+
+    let body = serde_json::to_vec(&problem).expect("problem details always serialize");
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(PROBLEM_CONTENT_TYPE),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
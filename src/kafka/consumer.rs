@@ -0,0 +1,278 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{BorrowedMessage, Headers, Message};
+use thiserror::Error;
+use tokio_stream::StreamExt;
+
+use crate::config::Config;
+use crate::db::mongo::MongoError;
+use crate::db::repository::DeadLetterRepository;
+use crate::dead_letter::models::DeadLetter;
+use crate::kafka::envelope::EventHeader;
+use crate::kafka::producer::{KafkaError, ProductEvent};
+use crate::kafka::publisher::EventPublisher;
+use crate::products::models::ProductResponse;
+
+/// Product event as seen by consumers. Deleted events carry no payload.
+pub type ConsumedProductEvent = ProductEvent<ProductResponse>;
+
+#[derive(Debug, Error)]
+pub enum HandlerError {
+    #[error("Repository error: {0}")]
+    Repository(#[from] MongoError),
+    #[error("{0}")]
+    Failed(String),
+}
+
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("Message has no payload")]
+    EmptyPayload,
+    #[error("Invalid product event: {0}")]
+    InvalidEvent(#[from] serde_json::Error),
+}
+
+#[async_trait]
+pub trait ProductEventHandler: Send + Sync {
+    fn name(&self) -> &str;
+    async fn handle(&self, event: &ConsumedProductEvent) -> Result<(), HandlerError>;
+}
+
+/// How often a failing handler is retried before the message is dead-lettered.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_retries: config.kafka_consumer_max_retries,
+            base_backoff: Duration::from_millis(config.kafka_consumer_retry_backoff_ms),
+        }
+    }
+
+    /// Delay before retry number `retry` (starting at 1).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.base_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+}
+
+pub fn decode_product_event(payload: Option<&[u8]>) -> Result<ConsumedProductEvent, DecodeError> {
+    let payload = payload.ok_or(DecodeError::EmptyPayload)?;
+    Ok(serde_json::from_slice(payload)?)
+}
+
+const MAX_DEAD_LETTER_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Reads product events from Kafka and hands each one to every registered
+/// handler. Failing handlers are retried according to the [`RetryPolicy`];
+/// messages that still fail, or cannot be decoded at all, are routed to the
+/// dead-letter topic so a single bad record cannot stop the consumer.
+///
+/// Offsets are committed manually, and only once a message has either been
+/// handled or safely dead-lettered, so delivery is at-least-once. Handlers
+/// must therefore be idempotent.
+pub struct EventConsumer {
+    consumer: StreamConsumer,
+    topics: Vec<String>,
+    handlers: Vec<Arc<dyn ProductEventHandler>>,
+    retry_policy: RetryPolicy,
+    dead_letter_topic: String,
+    publisher: Arc<dyn EventPublisher>,
+    dead_letters: Arc<dyn DeadLetterRepository>,
+}
+
+impl EventConsumer {
+    pub fn from_config(
+        config: &Config,
+        publisher: Arc<dyn EventPublisher>,
+        dead_letters: Arc<dyn DeadLetterRepository>,
+    ) -> Result<Self, KafkaError> {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("group.id", &config.kafka_consumer_group)
+            .set("bootstrap.servers", &config.kafka_brokers)
+            .set("auto.offset.reset", "earliest")
+            .set("enable.auto.commit", "false")
+            .create()
+            .map_err(KafkaError::ConsumerError)?;
+
+        Ok(Self {
+            consumer,
+            topics: vec![config.kafka_product_events_topic.clone()],
+            handlers: Vec::new(),
+            retry_policy: RetryPolicy::from_config(config),
+            dead_letter_topic: config.kafka_dead_letter_topic.clone(),
+            publisher,
+            dead_letters,
+        })
+    }
+
+    pub fn register(mut self, handler: impl ProductEventHandler + 'static) -> Self {
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+
+    pub async fn run(self) -> Result<(), KafkaError> {
+        let topics: Vec<&str> = self.topics.iter().map(String::as_str).collect();
+        self.consumer
+            .subscribe(&topics)
+            .map_err(KafkaError::ConsumerError)?;
+        tracing::info!("Listening to Kafka topics {:?}", topics);
+
+        let mut message_stream = self.consumer.stream();
+        while let Some(message_result) = message_stream.next().await {
+            match message_result {
+                Ok(message) => {
+                    self.process(&message).await;
+                    if let Err(e) = self.consumer.commit_message(&message, CommitMode::Async) {
+                        tracing::error!(
+                            "Failed to commit offset {}/{}@{}: {}",
+                            message.topic(),
+                            message.partition(),
+                            message.offset(),
+                            e
+                        );
+                    }
+                }
+                Err(e) => tracing::error!("Error while reading from stream: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn process(&self, message: &BorrowedMessage<'_>) {
+        let event = match decode_product_event(message.payload()) {
+            Ok(event) => event,
+            Err(e) => {
+                self.dead_letter(message, &e.to_string(), None, 1).await;
+                return;
+            }
+        };
+
+        for handler in &self.handlers {
+            if let Err((e, attempts)) = self.handle_with_retry(handler.as_ref(), &event).await {
+                self.dead_letter(message, &e.to_string(), Some(handler.name()), attempts)
+                    .await;
+            }
+        }
+    }
+
+    async fn handle_with_retry(
+        &self,
+        handler: &dyn ProductEventHandler,
+        event: &ConsumedProductEvent,
+    ) -> Result<(), (HandlerError, u32)> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match handler.handle(event).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempts > self.retry_policy.max_retries => return Err((e, attempts)),
+                Err(e) => {
+                    let delay = self.retry_policy.backoff(attempts);
+                    tracing::warn!(
+                        "Handler '{}' failed for event {} (attempt {}), retrying in {:?}: {}",
+                        handler.name(),
+                        event.metadata.event_id,
+                        attempts,
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    async fn dead_letter(
+        &self,
+        message: &BorrowedMessage<'_>,
+        error: &str,
+        handler: Option<&str>,
+        attempts: u32,
+    ) {
+        tracing::error!(
+            "Dead-lettering message at {}/{}@{} after {} attempt(s): {}",
+            message.topic(),
+            message.partition(),
+            message.offset(),
+            attempts,
+            error
+        );
+
+        let headers = message
+            .headers()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .map(|header| {
+                        EventHeader::new(
+                            header.key,
+                            String::from_utf8_lossy(header.value.unwrap_or_default()),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let dead_letter = DeadLetter {
+            _id: None,
+            source_topic: message.topic().to_string(),
+            source_partition: message.partition(),
+            source_offset: message.offset(),
+            key: message.key().map(|key| String::from_utf8_lossy(key).into_owned()),
+            payload: DeadLetter::payload_binary(message.payload().unwrap_or_default()),
+            headers,
+            error: error.to_string(),
+            handler: handler.map(str::to_string),
+            attempts,
+            failed_at: Utc::now(),
+            redrive_count: 0,
+            redriven_at: None,
+        };
+
+        let mut record = dead_letter.to_source_record();
+        record.topic = self.dead_letter_topic.clone();
+        record.headers.extend(dead_letter_headers(&dead_letter));
+
+        // The offset is committed once this returns, so keep trying until the
+        // message is safely on the dead-letter topic rather than dropping it.
+        let mut retry = 0;
+        while let Err(e) = self.publisher.publish(record.clone()).await {
+            retry += 1;
+            let delay = self.retry_policy.backoff(retry).min(MAX_DEAD_LETTER_BACKOFF);
+            tracing::error!(
+                "Failed to publish to dead-letter topic, retrying in {:?}: {:?}",
+                delay,
+                e
+            );
+            tokio::time::sleep(delay).await;
+        }
+        if let Err(e) = self.dead_letters.create_dead_letter(dead_letter).await {
+            tracing::error!("Failed to store dead letter: {:?}", e);
+        }
+    }
+}
+
+fn dead_letter_headers(dead_letter: &DeadLetter) -> Vec<EventHeader> {
+    let mut headers = vec![
+        EventHeader::new("dlq_error", &dead_letter.error),
+        EventHeader::new("dlq_source_topic", &dead_letter.source_topic),
+        EventHeader::new("dlq_source_partition", dead_letter.source_partition.to_string()),
+        EventHeader::new("dlq_source_offset", dead_letter.source_offset.to_string()),
+        EventHeader::new("dlq_attempts", dead_letter.attempts.to_string()),
+        EventHeader::new("dlq_failed_at", dead_letter.failed_at.to_rfc3339()),
+    ];
+    if let Some(handler) = &dead_letter.handler {
+        headers.push(EventHeader::new("dlq_handler", handler));
+    }
+    headers
+}
@@ -0,0 +1,113 @@
+use std::convert::Infallible;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::models::UserId;
+
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+pub const CAUSATION_ID_HEADER: &str = "x-causation-id";
+
+/// Standard metadata carried by every event we publish. It is flattened into
+/// the event body and mirrored in the Kafka record headers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EventMetadata {
+    pub event_id: Uuid,
+    pub schema_version: u32,
+    pub source: String,
+    pub actor_id: Option<String>,
+    pub correlation_id: String,
+    pub causation_id: Option<String>,
+}
+
+impl EventMetadata {
+    pub fn new(context: &EventContext, source: &str, schema_version: u32) -> Self {
+        Self {
+            event_id: Uuid::new_v4(),
+            schema_version,
+            source: source.to_string(),
+            actor_id: context.actor_id.clone(),
+            correlation_id: context.correlation_id.clone(),
+            causation_id: context.causation_id.clone(),
+        }
+    }
+
+    pub fn headers(&self, event_type: &str) -> Vec<EventHeader> {
+        let mut headers = vec![
+            EventHeader::new("event_id", self.event_id.to_string()),
+            EventHeader::new("event_type", event_type),
+            EventHeader::new("schema_version", self.schema_version.to_string()),
+            EventHeader::new("source", &self.source),
+            EventHeader::new("correlation_id", &self.correlation_id),
+            EventHeader::new("content_type", "application/json"),
+        ];
+        if let Some(causation_id) = &self.causation_id {
+            headers.push(EventHeader::new("causation_id", causation_id));
+        }
+        if let Some(actor_id) = &self.actor_id {
+            headers.push(EventHeader::new("actor_id", actor_id));
+        }
+        headers
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EventHeader {
+    pub key: String,
+    pub value: String,
+}
+
+impl EventHeader {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// Who triggered an event and which request chain it belongs to. Extracted
+/// from the authenticated user and the correlation headers of the request;
+/// a fresh correlation ID is generated when the caller did not send one.
+#[derive(Debug, Clone)]
+pub struct EventContext {
+    pub actor_id: Option<String>,
+    pub correlation_id: String,
+    pub causation_id: Option<String>,
+}
+
+impl EventContext {
+    pub fn new(actor_id: Option<String>) -> Self {
+        Self {
+            actor_id,
+            correlation_id: Uuid::new_v4().to_string(),
+            causation_id: None,
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for EventContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_string())
+        };
+
+        let mut context = EventContext::new(parts.extensions.get::<UserId>().map(|id| id.0.clone()));
+        if let Some(correlation_id) = header(CORRELATION_ID_HEADER) {
+            context.correlation_id = correlation_id;
+        }
+        context.causation_id = header(CAUSATION_ID_HEADER);
+        Ok(context)
+    }
+}
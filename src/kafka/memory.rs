@@ -0,0 +1,121 @@
+use crate::kafka::producer::KafkaError;
+use crate::kafka::publisher::{EventPublisher, EventRecord};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::broadcast;
+
+const SUBSCRIBER_CAPACITY: usize = 1024;
+/// Records kept by `new`. Without Kafka the broker lives as long as the
+/// server, so its history must not grow without bound.
+const DEFAULT_RETAINED_RECORDS: usize = 10_000;
+
+/// In-process stand-in for Kafka. The most recent records are kept in memory
+/// so they can be inspected later, and each is also fanned out to live
+/// subscribers.
+#[derive(Clone)]
+pub struct InMemoryBroker {
+    records: Arc<Mutex<VecDeque<EventRecord>>>,
+    retained: usize,
+    sender: broadcast::Sender<EventRecord>,
+}
+
+impl Default for InMemoryBroker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryBroker {
+    pub fn new() -> Self {
+        Self::with_retention(DEFAULT_RETAINED_RECORDS)
+    }
+
+    /// Keeps at most `retained` records, dropping the oldest first. Zero
+    /// keeps none, for a broker that only feeds subscribers.
+    pub fn with_retention(retained: usize) -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIBER_CAPACITY);
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::new())),
+            retained,
+            sender,
+        }
+    }
+
+    fn records(&self) -> MutexGuard<'_, VecDeque<EventRecord>> {
+        self.records.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<EventRecord> {
+        self.sender.subscribe()
+    }
+
+    pub fn published(&self) -> Vec<EventRecord> {
+        self.records().iter().cloned().collect()
+    }
+
+    pub fn published_to(&self, topic: &str) -> Vec<EventRecord> {
+        self.records()
+            .iter()
+            .filter(|record| record.topic == topic)
+            .cloned()
+            .collect()
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, topic: &str) -> Result<Vec<T>, KafkaError> {
+        self.published_to(topic)
+            .iter()
+            .map(|record| Ok(serde_json::from_slice(&record.payload)?))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.records().clear();
+    }
+}
+
+#[async_trait]
+impl EventPublisher for InMemoryBroker {
+    async fn publish(&self, record: EventRecord) -> Result<(), KafkaError> {
+        tracing::debug!("In-memory event published to topic '{}'", record.topic);
+        if self.retained > 0 {
+            let mut records = self.records();
+            if records.len() == self.retained {
+                records.pop_front();
+            }
+            records.push_back(record.clone());
+        }
+        // Having no subscribers is not an error; the record is still kept.
+        let _ = self.sender.send(record);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(key: &str) -> EventRecord {
+        EventRecord {
+            topic: "events".to_string(),
+            key: key.to_string(),
+            payload: Vec::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn drops_the_oldest_records_beyond_retention() {
+        let broker = InMemoryBroker::with_retention(2);
+        let mut subscriber = broker.subscribe();
+        for key in ["a", "b", "c"] {
+            broker.publish(record(key)).await.unwrap();
+        }
+
+        let kept: Vec<_> = broker.published().into_iter().map(|record| record.key).collect();
+        assert_eq!(kept, ["b", "c"]);
+        // Subscribers still see every record.
+        assert_eq!(subscriber.recv().await.unwrap().key, "a");
+    }
+}
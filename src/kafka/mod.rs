@@ -1 +1,5 @@
-pub mod producer;
\ No newline at end of file
+pub mod consumer;
+pub mod envelope;
+pub mod memory;
+pub mod producer;
+pub mod publisher;
\ No newline at end of file
@@ -1,7 +1,12 @@
+use crate::kafka::envelope::{EventContext, EventMetadata};
+use crate::kafka::publisher::{EventPublisher, EventRecord};
+use async_trait::async_trait;
 use rdkafka::config::ClientConfig;
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{FutureProducer, FutureRecord};
 use rdkafka::util::Timeout;
-use serde::Serialize;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use thiserror::Error;
 
@@ -9,32 +14,128 @@ use thiserror::Error;
 pub enum KafkaError {
     #[error("Kafka producer error: {0}")]
     ProducerError(#[from] rdkafka::error::KafkaError),
+    #[error("Kafka consumer error: {0}")]
+    ConsumerError(rdkafka::error::KafkaError),
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
     #[error("Kafka message delivery timed out")]
     DeliveryTimeout,
 }
 
-#[derive(Serialize, Debug, Clone)]
+pub const PRODUCT_EVENT_SCHEMA_VERSION: u32 = 7;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProductEventType {
     Created,
     Updated,
     Deleted,
+    /// A product taken back out of the trash. Added in schema version 5.
+    Restored,
+}
+
+impl ProductEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProductEventType::Created => "Created",
+            ProductEventType::Updated => "Updated",
+            ProductEventType::Deleted => "Deleted",
+            ProductEventType::Restored => "Restored",
+        }
+    }
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProductEvent<T> {
+    #[serde(flatten)]
+    pub metadata: EventMetadata,
     pub event_type: ProductEventType,
     pub product_id: String,
+    /// Owner of the product, so consumers can route events without a
+    /// payload (deletions) too. Added in schema version 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<String>,
     pub payload: Option<T>,
     #[serde(with = "chrono::serde::ts_milliseconds")]
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+impl<T> ProductEvent<T> {
+    pub fn new(
+        context: &EventContext,
+        source: &str,
+        event_type: ProductEventType,
+        product_id: String,
+        payload: Option<T>,
+    ) -> Self {
+        Self {
+            metadata: EventMetadata::new(context, source, PRODUCT_EVENT_SCHEMA_VERSION),
+            event_type,
+            product_id,
+            owner_id: None,
+            payload,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    pub fn with_owner(mut self, owner_id: Option<ObjectId>) -> Self {
+        self.owner_id = owner_id.map(|id| id.to_hex());
+        self
+    }
+}
+
+pub const USER_EVENT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum UserEventType {
+    Updated,
+    PasswordChanged,
+    Deleted,
+}
+
+impl UserEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserEventType::Updated => "Updated",
+            UserEventType::PasswordChanged => "PasswordChanged",
+            UserEventType::Deleted => "Deleted",
+        }
+    }
+}
+
+/// Account lifecycle event. The payload is the public profile and never
+/// carries credentials; password changes and deletions have none.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserEvent<T> {
+    #[serde(flatten)]
+    pub metadata: EventMetadata,
+    pub event_type: UserEventType,
+    pub user_id: String,
+    pub payload: Option<T>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl<T> UserEvent<T> {
+    pub fn new(
+        context: &EventContext,
+        source: &str,
+        event_type: UserEventType,
+        user_id: String,
+        payload: Option<T>,
+    ) -> Self {
+        Self {
+            metadata: EventMetadata::new(context, source, USER_EVENT_SCHEMA_VERSION),
+            event_type,
+            user_id,
+            payload,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AppKafkaProducer {
     pub producer: FutureProducer,
-    pub topic: String,
 }
 
 impl AppKafkaProducer {
@@ -42,29 +143,36 @@ impl AppKafkaProducer {
         let producer: FutureProducer = ClientConfig::new()
             .set("bootstrap.servers", brokers)
             .set("message.timeout.ms", "5000")
+            // Keeps per-key ordering intact when sends are retried, which
+            // batches rely on with several records in flight.
+            .set("enable.idempotence", "true")
             .create()?;
         println!("Kafka producer created successfully.");
-        Ok(Self {
-            producer,
-            topic: String::new(),
-        })
+        Ok(Self { producer })
     }
+}
 
-    pub async fn send_product_event<T: Serialize>(
-        &self,
-        topic: &str,
-        event: ProductEvent<T>,
-    ) -> Result<(), KafkaError> {
-        let payload = serde_json::to_string(&event)?;
-        let key = event.product_id.clone();
+fn kafka_headers(record: &EventRecord) -> OwnedHeaders {
+    record.headers.iter().fold(OwnedHeaders::new(), |headers, header| {
+        headers.insert(Header {
+            key: &header.key,
+            value: Some(&header.value),
+        })
+    })
+}
 
-        let record = FutureRecord::to(topic)
-            .payload(&payload)
-            .key(&key);
+#[async_trait]
+impl EventPublisher for AppKafkaProducer {
+    async fn publish(&self, record: EventRecord) -> Result<(), KafkaError> {
+        let headers = kafka_headers(&record);
+        let kafka_record = FutureRecord::to(&record.topic)
+            .payload(&record.payload)
+            .key(&record.key)
+            .headers(headers);
 
-        match self.producer.send(record, Timeout::After(Duration::from_secs(5))).await {
+        match self.producer.send(kafka_record, Timeout::After(Duration::from_secs(5))).await {
             Ok(_) => {
-                tracing::debug!("Kafka message sent successfully to topic '{}'", topic);
+                tracing::debug!("Kafka message sent successfully to topic '{}'", record.topic);
                 Ok(())
             },
             Err((kafka_err, _owned_message)) => {
@@ -73,4 +181,38 @@ impl AppKafkaProducer {
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Enqueues every record before waiting for any acknowledgement, so a
+    /// batch costs roughly one round trip instead of one per record.
+    async fn publish_batch(&self, records: Vec<EventRecord>) -> Vec<Result<(), KafkaError>> {
+        let deliveries: Vec<_> = records
+            .iter()
+            .map(|record| {
+                let kafka_record = FutureRecord::to(&record.topic)
+                    .payload(&record.payload)
+                    .key(&record.key)
+                    .headers(kafka_headers(record));
+                self.producer
+                    .send_result(kafka_record)
+                    .map_err(|(kafka_err, _record)| kafka_err)
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(deliveries.len());
+        for delivery in deliveries {
+            let result = match delivery {
+                Ok(future) => match future.await {
+                    Ok(Ok(_)) => Ok(()),
+                    Ok(Err((kafka_err, _message))) => Err(kafka_err),
+                    Err(_canceled) => Err(rdkafka::error::KafkaError::Canceled),
+                },
+                Err(kafka_err) => Err(kafka_err),
+            };
+            if let Err(kafka_err) = &result {
+                tracing::error!("Failed to deliver batched message to Kafka: {}", kafka_err);
+            }
+            results.push(result.map_err(KafkaError::ProducerError));
+        }
+        results
+    }
+}
@@ -0,0 +1,55 @@
+use crate::kafka::envelope::EventHeader;
+use crate::kafka::producer::{KafkaError, ProductEvent, UserEvent};
+use async_trait::async_trait;
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub struct EventRecord {
+    pub topic: String,
+    pub key: String,
+    pub payload: Vec<u8>,
+    pub headers: Vec<EventHeader>,
+}
+
+impl EventRecord {
+    pub fn product_event<T: Serialize>(
+        topic: &str,
+        event: &ProductEvent<T>,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            topic: topic.to_string(),
+            key: event.product_id.clone(),
+            payload: serde_json::to_vec(event)?,
+            headers: event.metadata.headers(event.event_type.as_str()),
+        })
+    }
+
+    pub fn user_event<T: Serialize>(
+        topic: &str,
+        event: &UserEvent<T>,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            topic: topic.to_string(),
+            key: event.user_id.clone(),
+            payload: serde_json::to_vec(event)?,
+            headers: event.metadata.headers(event.event_type.as_str()),
+        })
+    }
+}
+
+/// Destination for domain events. Implemented by the Kafka producer and by the
+/// in-process broker used when Kafka is disabled.
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, record: EventRecord) -> Result<(), KafkaError>;
+
+    /// Publishes `records` in order, returning one result per record.
+    /// Records sharing a key must reach the broker in the order given.
+    async fn publish_batch(&self, records: Vec<EventRecord>) -> Vec<Result<(), KafkaError>> {
+        let mut results = Vec::with_capacity(records.len());
+        for record in records {
+            results.push(self.publish(record).await);
+        }
+        results
+    }
+}
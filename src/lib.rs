@@ -1,7 +1,13 @@
+pub mod app;
 pub mod config;
+pub mod error;
 pub mod state;
+pub mod validation;
 pub mod db;
 pub mod kafka;
+pub mod mail;
+pub mod outbox;
+pub mod dead_letter;
 pub mod auth;
 pub mod products;
 pub mod message;
\ No newline at end of file
@@ -0,0 +1,95 @@
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
+
+use crate::db::{mongo::MongoError, repository::Repository};
+use crate::mail::models::OutgoingEmail;
+
+#[derive(Debug, Error)]
+pub enum MailerError {
+    #[error("Failed to store email: {0}")]
+    Store(#[from] MongoError),
+    #[error("Failed to write email file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to serialize email: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Where outgoing emails go, from `MAILER`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailerKind {
+    Mongo,
+    File,
+}
+
+impl FromStr for MailerKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "mongo" => Ok(MailerKind::Mongo),
+            "file" => Ok(MailerKind::File),
+            other => Err(format!("unknown mailer '{}'", other)),
+        }
+    }
+}
+
+/// Sends account emails. Implement this to deliver through a real provider;
+/// the built-in mailers only record messages so flows work without SMTP.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: OutgoingEmail) -> Result<(), MailerError>;
+}
+
+/// Stores emails in the `outgoing_emails` collection.
+pub struct RepositoryMailer {
+    db_repo: Arc<dyn Repository>,
+}
+
+impl RepositoryMailer {
+    pub fn new(db_repo: Arc<dyn Repository>) -> Self {
+        Self { db_repo }
+    }
+}
+
+#[async_trait]
+impl Mailer for RepositoryMailer {
+    async fn send(&self, email: OutgoingEmail) -> Result<(), MailerError> {
+        self.db_repo.enqueue_email(email).await?;
+        Ok(())
+    }
+}
+
+/// Appends each email as one JSON line to a local file.
+pub struct FileMailer {
+    path: PathBuf,
+    // Serializes appends so concurrent emails never interleave.
+    lock: Mutex<()>,
+}
+
+impl FileMailer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: OutgoingEmail) -> Result<(), MailerError> {
+        let mut line = serde_json::to_vec(&email)?;
+        line.push(b'\n');
+        let _guard = self.lock.lock().await;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+pub mod mailer;
+pub mod models;
\ No newline at end of file
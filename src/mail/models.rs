@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+/// A message handed to a `Mailer`. The default mailer stores these in the
+/// `outgoing_emails` collection, where a delivery worker can pick them up.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutgoingEmail {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl OutgoingEmail {
+    pub fn new(from: &str, to: &str, subject: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            _id: Some(ObjectId::new()),
+            from: from.to_string(),
+            to: to.to_string(),
+            subject: subject.into(),
+            body: body.into(),
+            created_at: Utc::now(),
+        }
+    }
+}
@@ -1,50 +1,14 @@
-use axum::middleware;
 use rs_kafka_mongo::{
-    auth::{self},
-    config::Config,
-    message,
-    products::{self},
+    app::build_router, auth::bootstrap::ensure_bootstrap_admin, config::Config,
+    db::mongo::MongoRepo, outbox::relay::OutboxRelay, products::purge::TrashPurger,
     state::AppState,
 };
-use tower_http::{
-    cors::{Any, CorsLayer},
-    trace::TraceLayer,
-};
+use std::net::SocketAddr;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use utoipa::{
-    Modify, OpenApi,
-    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
-};
-use utoipa_axum::{router::OpenApiRouter, routes};
 use utoipa_swagger_ui::SwaggerUi;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    #[derive(OpenApi)]
-    #[openapi(
-        info(description = "This project is a simple yet complete API built in Rust, demonstrating user authentication and full CRUD operations for products. It integrates MongoDB for persistent storage and uses Kafka to stream product-related events. The entire application is containerized with Docker for easy deployment, and Swagger UI is included to provide a clear and interactive interface for testing the API endpoints."),
-        modifiers(&SecurityAddon),
-        tags(
-            (name = "product", description = "product api management"),
-            (name = "message", description = "message api management"),
-            (name = "user", description = "user api management")
-        )
-    )]
-    struct ApiDoc;
-
-    struct SecurityAddon;
-
-    impl Modify for SecurityAddon {
-        fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
-            if let Some(components) = openapi.components.as_mut() {
-                components.add_security_scheme(
-                    "token",
-                    SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
-                )
-            }
-        }
-    }
-
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG")
@@ -55,58 +19,63 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let config = Config::from_env()?;
 
+    if let Some(command) = std::env::args().nth(1) {
+        return run_command(&command, &config).await;
+    }
+
     let app_state = AppState::new(config.clone()).await?;
+    ensure_bootstrap_admin(app_state.db_repo.as_ref(), &config).await?;
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    OutboxRelay::new(
+        app_state.db_repo.clone(),
+        app_state.event_publisher.clone(),
+        &config,
+    )
+    .spawn();
+    TrashPurger::new(app_state.db_repo.clone(), &config).spawn();
 
-    let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
-        .nest("/products", product_routes(app_state.clone()))
-        .nest("/messages", message_routes(app_state.clone()))
-        .layer(middleware::from_fn_with_state(
-            app_state.clone(),
-            auth::middleware::auth_middleware,
-        ))
-        .nest("/auth", auth_routes(app_state.clone()))
-        .layer(TraceLayer::new_for_http())
-        .layer(cors)
-        .split_for_parts();
+    let (router, api) = build_router(app_state);
 
     let router =
         router.merge(SwaggerUi::new("/").url("/api-docs/openapi.json", api.clone()));
 
     let listener = tokio::net::TcpListener::bind(&config.server_addr).await?;
     tracing::info!("Server listening on {}", config.server_addr);
-    axum::serve(listener, router.into_make_service()).await?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
-fn auth_routes(app_state: AppState) -> OpenApiRouter {
-    OpenApiRouter::new()
-        .routes(routes!(auth::handlers::signup))
-        .routes(routes!(auth::handlers::login))
-        .with_state(app_state)
-}
-
-fn product_routes(app_state: AppState) -> OpenApiRouter {
-    OpenApiRouter::new()
-        .routes(routes!(
-            products::handlers::list_products,
-            products::handlers::create_product,
-        ))
-        .routes(routes!(
-            products::handlers::delete_product,
-            products::handlers::update_product,
-            products::handlers::get_product
-        ))
-        .with_state(app_state)
-}
-
-fn message_routes(app_state: AppState) -> OpenApiRouter {
-    OpenApiRouter::new()
-        .routes(routes!(message::handlers::list_messages))
-        .with_state(app_state)
+async fn run_command(command: &str, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        "migrate" => {
+            let db_repo = MongoRepo::init(&config.database_url, &config.database_name).await?;
+            let applied = db_repo.migrate(config).await?;
+            for record in &applied {
+                println!("applied {:>4} {}", record.version, record.name);
+            }
+            println!("{} migration(s) applied", applied.len());
+        }
+        "migrate-status" => {
+            let db_repo = MongoRepo::init(&config.database_url, &config.database_name).await?;
+            for record in db_repo.applied_migrations(config).await? {
+                println!(
+                    "{:>4} {} (applied {})",
+                    record.version, record.name, record.applied_at
+                );
+            }
+        }
+        other => {
+            return Err(format!(
+                "unknown command `{}`, expected `migrate` or `migrate-status`",
+                other
+            )
+            .into());
+        }
+    }
+    Ok(())
 }
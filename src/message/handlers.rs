@@ -1,6 +1,6 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 
-use crate::{message::models::MessageResponse, state::AppState};
+use crate::{error::AppError, message::models::MessageResponse, state::AppState};
 
 use super::utils::message_to_responses;
 
@@ -12,22 +12,13 @@ use super::utils::message_to_responses;
       (status = 200, description = "List all message successfully", body = [MessageResponse])
   ),
   security(
-      ("token" = [])
+      ("token" = ["messages:read"]),
+      ("api_key" = ["messages:read"])
   )
 )]
 pub async fn list_messages(
   State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-  match state.db_repo.find_all_message().await {
-      Ok(messages) => {
-          Ok((StatusCode::OK, Json(message_to_responses(&messages))))
-      }
-      Err(e) => {
-          tracing::error!("Failed to list products: {:?}", e);
-          Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "Failed to retrieve products".to_string(),
-          ))
-      }
-  }
+) -> Result<impl IntoResponse, AppError> {
+  let messages = state.db_repo.find_all_message().await?;
+  Ok((StatusCode::OK, Json(message_to_responses(&messages))))
 }
\ No newline at end of file
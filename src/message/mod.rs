@@ -1,3 +1,4 @@
 pub mod models;
 pub mod handlers;
+pub mod recorder;
 pub mod utils;
\ No newline at end of file
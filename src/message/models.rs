@@ -1,4 +1,5 @@
 use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -6,20 +7,36 @@ use utoipa::ToSchema;
 pub struct Message {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub _id: Option<ObjectId>,
-    pub message: String
+    pub message: String,
+    #[serde(default)]
+    pub event_id: Option<String>,
+    #[serde(default)]
+    pub event_type: Option<String>,
+    #[serde(default)]
+    pub product_id: Option<String>,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub received_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct MessageResponse {
     pub id: String,
-    pub message: String
+    pub message: String,
+    pub event_id: Option<String>,
+    pub event_type: Option<String>,
+    pub product_id: Option<String>,
+    pub received_at: Option<String>,
 }
 
 impl MessageResponse {
     pub fn from_message(message: &Message) -> Self {
         MessageResponse {
             id: message._id.expect("Product from DB must have an ID").to_hex(),
-            message: message.message.clone()
+            message: message.message.clone(),
+            event_id: message.event_id.clone(),
+            event_type: message.event_type.clone(),
+            product_id: message.product_id.clone(),
+            received_at: message.received_at.map(|at| at.to_string()),
         }
     }
-}
\ No newline at end of file
+}
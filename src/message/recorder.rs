@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+
+use crate::db::repository::MessageRepository;
+use crate::kafka::consumer::{ConsumedProductEvent, HandlerError, ProductEventHandler};
+
+use super::models::Message;
+
+/// Stores every consumed product event in the `messages` collection.
+pub struct MessageRecorder {
+    repo: Arc<dyn MessageRepository>,
+}
+
+impl MessageRecorder {
+    pub fn new(repo: Arc<dyn MessageRepository>) -> Self {
+        Self { repo }
+    }
+}
+
+#[async_trait]
+impl ProductEventHandler for MessageRecorder {
+    fn name(&self) -> &str {
+        "message-recorder"
+    }
+
+    async fn handle(&self, event: &ConsumedProductEvent) -> Result<(), HandlerError> {
+        let message = serde_json::to_string(event)
+            .map_err(|e| HandlerError::Failed(format!("Failed to encode event: {}", e)))?;
+        let new_message = Message {
+            _id: Some(ObjectId::new()),
+            message,
+            event_id: Some(event.metadata.event_id.to_string()),
+            event_type: Some(event.event_type.as_str().to_string()),
+            product_id: Some(event.product_id.clone()),
+            received_at: Some(chrono::Utc::now()),
+        };
+        if !self.repo.create_message_if_absent(new_message).await? {
+            tracing::info!(
+                "Skipping already recorded event {}",
+                event.metadata.event_id
+            );
+            return Ok(());
+        }
+        tracing::info!(
+            "Recorded {} event for product {}",
+            event.event_type.as_str(),
+            event.product_id
+        );
+        Ok(())
+    }
+}
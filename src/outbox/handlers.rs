@@ -0,0 +1,22 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{error::AppError, outbox::models::OutboxStatsResponse, state::AppState};
+
+#[utoipa::path(
+  get,
+  path = "",
+  tag = "outbox",
+  responses(
+      (status = 200, description = "Outbox backlog size", body = OutboxStatsResponse)
+  ),
+  security(
+      ("token" = ["admin"]),
+      ("api_key" = ["admin"])
+  )
+)]
+pub async fn outbox_stats(
+  State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+  let pending = state.db_repo.count_pending_outbox().await?;
+  Ok((StatusCode::OK, Json(OutboxStatsResponse { pending })))
+}
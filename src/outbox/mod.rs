@@ -0,0 +1,3 @@
+pub mod models;
+pub mod handlers;
+pub mod relay;
\ No newline at end of file
@@ -0,0 +1,104 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::kafka::envelope::EventHeader;
+use crate::auth::models::User;
+use crate::kafka::producer::{ProductEvent, UserEvent};
+use crate::kafka::publisher::EventRecord;
+use crate::products::models::Product;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum OutboxStatus {
+    Pending,
+    Delivered,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "Pending",
+            OutboxStatus::Delivered => "Delivered",
+        }
+    }
+}
+
+/// An event waiting to be relayed to the broker. It is written in the same
+/// transaction as the change it describes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub topic: String,
+    pub key: String,
+    pub payload: String,
+    #[serde(default)]
+    pub headers: Vec<EventHeader>,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(default, with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxEntry {
+    pub fn new(record: EventRecord) -> Self {
+        let now = Utc::now();
+        Self {
+            _id: Some(ObjectId::new()),
+            topic: record.topic,
+            key: record.key,
+            payload: String::from_utf8_lossy(&record.payload).into_owned(),
+            headers: record.headers,
+            status: OutboxStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            created_at: now,
+            next_attempt_at: now,
+            delivered_at: None,
+        }
+    }
+
+    pub fn for_product_event<T: Serialize>(
+        topic: &str,
+        event: &ProductEvent<T>,
+    ) -> Result<Self, serde_json::Error> {
+        EventRecord::product_event(topic, event).map(Self::new)
+    }
+
+    pub fn for_user_event<T: Serialize>(
+        topic: &str,
+        event: &UserEvent<T>,
+    ) -> Result<Self, serde_json::Error> {
+        EventRecord::user_event(topic, event).map(Self::new)
+    }
+
+    pub fn to_record(&self) -> EventRecord {
+        EventRecord {
+            topic: self.topic.clone(),
+            key: self.key.clone(),
+            payload: self.payload.clone().into_bytes(),
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+/// Builds the outbox entry for a product update once the updated document is
+/// known, inside the same transaction as the update itself. It may be called
+/// again if the transaction is retried.
+pub type ProductOutboxFactory =
+    Box<dyn Fn(&Product) -> Result<OutboxEntry, serde_json::Error> + Send + Sync>;
+
+/// Same as `ProductOutboxFactory`, for profile updates.
+pub type UserOutboxFactory =
+    Box<dyn FnOnce(&User) -> Result<OutboxEntry, serde_json::Error> + Send>;
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct OutboxStatsResponse {
+    pub pending: u64,
+}
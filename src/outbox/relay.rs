@@ -0,0 +1,229 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::db::mongo::MongoError;
+use crate::db::repository::Repository;
+use crate::kafka::publisher::EventPublisher;
+use crate::outbox::models::OutboxEntry;
+
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Background task that publishes pending outbox entries and records the
+/// outcome of each attempt. Failed entries are retried with exponential
+/// backoff until they are delivered.
+#[derive(Clone)]
+pub struct OutboxRelay {
+    repo: Arc<dyn Repository>,
+    publisher: Arc<dyn EventPublisher>,
+    poll_interval: Duration,
+    batch_size: i64,
+    max_backoff: Duration,
+}
+
+impl OutboxRelay {
+    pub fn new(
+        repo: Arc<dyn Repository>,
+        publisher: Arc<dyn EventPublisher>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            repo,
+            publisher,
+            poll_interval: Duration::from_millis(config.outbox_poll_interval_ms),
+            batch_size: config.outbox_batch_size,
+            max_backoff: Duration::from_secs(config.outbox_max_backoff_secs),
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                interval.tick().await;
+                match self.run_once().await {
+                    Ok(0) => {}
+                    Ok(delivered) => tracing::debug!("Outbox relay delivered {} events", delivered),
+                    Err(e) => tracing::error!("Outbox relay failed to read pending events: {:?}", e),
+                }
+            }
+        })
+    }
+
+    /// Publishes one batch of due entries and returns how many were delivered.
+    ///
+    /// Entries go out in rounds holding the oldest remaining entry of each
+    /// key. Once an entry fails, the rest of its key waits for the retry, so
+    /// no event overtakes one it follows.
+    pub async fn run_once(&self) -> Result<usize, MongoError> {
+        let entries = self.repo.find_due_outbox_entries(self.batch_size).await?;
+        let mut queues: Vec<VecDeque<OutboxEntry>> = Vec::new();
+        let mut queue_of_key: HashMap<String, usize> = HashMap::new();
+        for entry in entries {
+            let queue = *queue_of_key.entry(entry.key.clone()).or_insert_with(|| {
+                queues.push(VecDeque::new());
+                queues.len() - 1
+            });
+            queues[queue].push_back(entry);
+        }
+
+        let mut delivered = 0;
+        loop {
+            let round: Vec<(usize, OutboxEntry)> = queues
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(queue, entries)| Some((queue, entries.pop_front()?)))
+                .collect();
+            if round.is_empty() {
+                break;
+            }
+            let records = round.iter().map(|(_, entry)| entry.to_record()).collect();
+            let results = self.publisher.publish_batch(records).await;
+
+            for ((queue, entry), result) in round.into_iter().zip(results) {
+                if result.is_err() {
+                    queues[queue].clear();
+                }
+                let Some(id) = entry._id else { continue };
+                match result {
+                    Ok(()) => {
+                        self.repo.mark_outbox_delivered(id).await?;
+                        delivered += 1;
+                    }
+                    Err(e) => {
+                        let delay = self.backoff(entry.attempts);
+                        tracing::warn!(
+                            "Failed to relay outbox entry {} (attempt {}), retrying in {:?}: {}",
+                            id,
+                            entry.attempts + 1,
+                            delay,
+                            e
+                        );
+                        let next_attempt_at = Utc::now()
+                            + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::MAX);
+                        self.repo
+                            .mark_outbox_failed(id, &e.to_string(), next_attempt_at)
+                            .await?;
+                    }
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    fn backoff(&self, attempts: u32) -> Duration {
+        BASE_BACKOFF
+            .saturating_mul(2u32.saturating_pow(attempts))
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use mongodb::bson::oid::ObjectId;
+
+    use super::*;
+    use crate::db::memory::InMemoryRepo;
+    use crate::db::repository::{OutboxRepository, ProductRepository};
+    use crate::kafka::memory::InMemoryBroker;
+    use crate::kafka::producer::KafkaError;
+    use crate::kafka::publisher::EventRecord;
+    use crate::products::{handlers::new_product, models::CreateProductRequest};
+
+    /// Fails records whose payload is listed, and hands the rest to `broker`.
+    struct FailingPublisher {
+        broker: InMemoryBroker,
+        failing: Vec<&'static str>,
+    }
+
+    #[async_trait]
+    impl EventPublisher for FailingPublisher {
+        async fn publish(&self, record: EventRecord) -> Result<(), KafkaError> {
+            if self.failing.iter().any(|payload| payload.as_bytes() == record.payload) {
+                return Err(KafkaError::DeliveryTimeout);
+            }
+            self.broker.publish(record).await
+        }
+    }
+
+    fn relay(repo: &InMemoryRepo, publisher: FailingPublisher) -> OutboxRelay {
+        OutboxRelay {
+            repo: Arc::new(repo.clone()),
+            publisher: Arc::new(publisher),
+            poll_interval: Duration::from_secs(1),
+            batch_size: 100,
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+
+    /// Stores a product whose outbox entry is keyed `key` and carries `payload`.
+    async fn enqueue(repo: &InMemoryRepo, key: &str, payload: &str) {
+        let request = CreateProductRequest {
+            sku: None,
+            name: payload.to_string(),
+            description: String::new(),
+            price: "1.00".to_string(),
+            currency: None,
+        };
+        let product = new_product(request, ObjectId::new(), "USD").unwrap();
+        let entry = OutboxEntry::new(EventRecord {
+            topic: "products".to_string(),
+            key: key.to_string(),
+            payload: payload.as_bytes().to_vec(),
+            headers: Vec::new(),
+        });
+        repo.create_product(product, entry).await.unwrap();
+    }
+
+    fn payloads(broker: &InMemoryBroker) -> Vec<String> {
+        broker
+            .published()
+            .into_iter()
+            .map(|record| String::from_utf8(record.payload).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn failed_entry_holds_back_later_entries_of_its_key() {
+        let repo = InMemoryRepo::new();
+        enqueue(&repo, "a", "a1").await;
+        enqueue(&repo, "a", "a2").await;
+        enqueue(&repo, "b", "b1").await;
+        let broker = InMemoryBroker::new();
+        let failing = FailingPublisher {
+            broker: broker.clone(),
+            failing: vec!["a1"],
+        };
+        let relay = relay(&repo, failing);
+
+        assert_eq!(relay.run_once().await.unwrap(), 1);
+        assert_eq!(payloads(&broker), ["b1"]);
+        // `a1` now waits for its retry, and `a2` with it.
+        assert_eq!(relay.run_once().await.unwrap(), 0);
+        assert_eq!(payloads(&broker), ["b1"]);
+        assert_eq!(repo.count_pending_outbox().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn entries_of_a_key_are_published_in_order() {
+        let repo = InMemoryRepo::new();
+        enqueue(&repo, "a", "a1").await;
+        enqueue(&repo, "b", "b1").await;
+        enqueue(&repo, "a", "a2").await;
+        enqueue(&repo, "a", "a3").await;
+        let broker = InMemoryBroker::new();
+        let failing = FailingPublisher {
+            broker: broker.clone(),
+            failing: vec!["a2"],
+        };
+
+        assert_eq!(relay(&repo, failing).run_once().await.unwrap(), 2);
+        assert_eq!(payloads(&broker), ["a1", "b1"]);
+    }
+}
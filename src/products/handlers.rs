@@ -1,80 +1,322 @@
 use crate::{
-    kafka::producer::{ProductEvent, ProductEventType},
+    auth::models::{Grants, Scope, UserId},
+    error::{AppError, ProblemDetails, parse_object_id},
+    kafka::{
+        envelope::EventContext,
+        producer::{ProductEvent, ProductEventType},
+    },
+    outbox::models::{OutboxEntry, ProductOutboxFactory},
     products::{
         models::{
-            CreateProductRequest, Product, ProductResponse, UpdateProductRequest,
+            BulkItemResult, BulkOperation, BulkProductRequest, BulkProductResponse,
+            CreateProductRequest, ExportProductsParams, ImportJob, ImportJobResponse,
+            ImportProductsParams, ImportStatus, ListProductsParams, ListTrashParams, Product, ProductCursor,
+            ProductFileFormat, ProductFilter, ProductPage, ProductQuery, ProductResponse, ProductSearchHit,
+            ProductSearchPage, ProductSearchQuery, ProductSortField, SearchCursor,
+            ProductWrite, ProductWriteOutcome, SearchProductsParams, SortOrder, UpdateProductRequest,
+        },
+        import::ProductImporter,
+        money::Currency,
+        utils::{
+            decode_cursor, encode_cursor, etag, highlight_product, if_match_allows,
+            price_error, product_csv_header, product_csv_record, product_json_line,
+            products_to_responses, search_terms, validate_currency,
         },
-        utils::{products_to_responses, send_kafka_event},
     },
     state::AppState,
+    validation::ValidatedJson,
 };
 use axum::{
-    Json,
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    Extension, Json,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use bson::{Bson, DateTime};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use mongodb::bson::{Document, oid::ObjectId};
-use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
 use tracing::{error, info, warn};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+fn validate_limit(limit: Option<u32>) -> Result<u32, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    Ok(limit)
+}
+
+/// Owner named by an `owner` query parameter: a user id, or `me`.
+fn owner_filter(owner: Option<&str>, user_id: &UserId) -> Result<Option<ObjectId>, AppError> {
+    match owner {
+        None => Ok(None),
+        Some("me") => Ok(Some(user_id.object_id()?)),
+        Some(owner) => Ok(Some(parse_object_id(owner, "owner")?)),
+    }
+}
+
+/// A failed check on one request field, reported like those found by
+/// `Validate`.
+pub(crate) fn field_error(field: &'static str, error: ValidationError) -> AppError {
+    let mut errors = ValidationErrors::new();
+    errors.add(field, error);
+    AppError::InvalidFields(errors)
+}
+
+/// `price` in minor units of `currency`. Requests have checked the format
+/// already; what remains is the currency's own number of decimal places.
+pub(crate) fn price_in(price: &str, currency: &str) -> Result<(i64, Currency), AppError> {
+    validate_currency(currency).map_err(|e| field_error("currency", e))?;
+    let currency = Currency::from_code(currency).expect("currency was just validated");
+    let price_minor = currency
+        .parse_amount(price)
+        .map_err(|e| field_error("price", price_error(e)))?;
+    Ok((price_minor, currency))
+}
+
+/// Products priced without a currency get `default_currency`.
+pub(crate) fn new_product(
+    payload: CreateProductRequest,
+    owner: ObjectId,
+    default_currency: &str,
+) -> Result<Product, AppError> {
+    let currency = payload.currency.as_deref().unwrap_or(default_currency);
+    let (price_minor, currency) = price_in(&payload.price, currency)?;
+    let now = chrono::Utc::now();
+    Ok(Product {
+        _id: Some(ObjectId::new()),
+        sku: payload.sku,
+        name: payload.name,
+        description: payload.description,
+        price_minor,
+        currency: currency.code.to_string(),
+        owner_id: Some(owner),
+        created_by: Some(owner),
+        updated_by: Some(owner),
+        created_at: now,
+        updated_at: now,
+        version: 1,
+        deleted_at: None,
+        deleted_by: None,
+    })
+}
+
+/// `$set` document for the fields present in `payload`, or `None` if it
+/// changes nothing. A price without a currency stays in `current_currency`.
+pub(crate) fn update_document(
+    payload: UpdateProductRequest,
+    updated_by: ObjectId,
+    current_currency: &str,
+) -> Result<Option<Document>, AppError> {
+    let mut update_doc = Document::new();
+    if let Some(sku) = payload.sku {
+        update_doc.insert("sku", sku);
+    }
+    if let Some(name) = payload.name {
+        update_doc.insert("name", name);
+    }
+    if let Some(desc) = payload.description {
+        update_doc.insert("description", desc);
+    }
+    match (payload.price, payload.currency) {
+        (Some(price), currency) => {
+            let currency = currency.as_deref().unwrap_or(current_currency);
+            let (price_minor, currency) = price_in(&price, currency)?;
+            update_doc.insert("price_minor", price_minor);
+            update_doc.insert("currency", currency.code);
+        }
+        // Reading the stored amount in another currency would quietly change
+        // the price.
+        (None, Some(currency)) if !currency.eq_ignore_ascii_case(current_currency) => {
+            return Err(field_error(
+                "currency",
+                ValidationError::new("requires_price")
+                    .with_message("can only be changed together with price".into()),
+            ));
+        }
+        (None, _) => {}
+    }
+    if update_doc.is_empty() {
+        return Ok(None);
+    }
+    update_doc.insert("updated_at", Bson::DateTime(DateTime::now()));
+    update_doc.insert("updated_by", updated_by);
+    Ok(Some(update_doc))
+}
+
+pub(crate) fn sku_conflict(sku: &str, holder: &Product) -> AppError {
+    let in_trash = if holder.deleted_at.is_some() { " in the trash" } else { "" };
+    AppError::Conflict(format!(
+        "SKU {} is already used by product {}{}",
+        sku,
+        holder._id.map(|id| id.to_hex()).unwrap_or_default(),
+        in_trash
+    ))
+}
+
+/// `sku` passed its check, but another product took it before the write.
+pub(crate) fn sku_taken_meanwhile(sku: Option<&str>) -> AppError {
+    AppError::Conflict(format!(
+        "SKU {} was taken by another product meanwhile",
+        sku.unwrap_or_default()
+    ))
+}
+
+/// Rejects `sku` if a product other than `id` holds it. Trashed products
+/// count, since restoring them would otherwise clash.
+async fn ensure_sku_available(
+    state: &AppState,
+    sku: Option<&str>,
+    id: Option<ObjectId>,
+) -> Result<(), AppError> {
+    let Some(sku) = sku else {
+        return Ok(());
+    };
+    let holders = state.db_repo.find_products_by_skus(&[sku.to_string()]).await?;
+    match holders.iter().find(|holder| holder._id != id) {
+        Some(holder) => Err(sku_conflict(sku, holder)),
+        None => Ok(()),
+    }
+}
+
+fn check_if_match(if_match: Option<&str>, product: &Product) -> Result<(), AppError> {
+    if if_match.is_some_and(|if_match| !if_match_allows(if_match, product.version)) {
+        warn!(
+            "If-Match mismatch on product {:?} at version {}",
+            product._id, product.version
+        );
+        return Err(AppError::PreconditionFailed(format!(
+            "Product has changed; its current ETag is {}",
+            etag(product.version)
+        )));
+    }
+    Ok(())
+}
+
+/// The product as JSON, with its version as the `ETag`.
+fn product_with_etag(status: StatusCode, product: &Product) -> Response {
+    (
+        status,
+        [(header::ETAG, etag(product.version))],
+        Json(ProductResponse::from_product(product)),
+    )
+        .into_response()
+}
+
+/// Outbox entry announcing `event_type` for `product`. Deletions carry no
+/// payload.
+fn product_event_entry(
+    topic: &str,
+    source: &str,
+    event_context: &EventContext,
+    event_type: ProductEventType,
+    product: &Product,
+) -> Result<OutboxEntry, serde_json::Error> {
+    let payload = (event_type != ProductEventType::Deleted)
+        .then(|| ProductResponse::from_product(product));
+    let event = ProductEvent::new(
+        event_context,
+        source,
+        event_type,
+        product._id.expect("Product must have an ID").to_hex(),
+        payload,
+    )
+    .with_owner(product.owner_id);
+    OutboxEntry::for_product_event(topic, &event)
+}
+
+pub(crate) fn product_event(
+    state: &AppState,
+    event_context: &EventContext,
+    event_type: ProductEventType,
+    product: &Product,
+) -> Result<OutboxEntry, serde_json::Error> {
+    product_event_entry(
+        &state.config.kafka_product_events_topic,
+        &state.config.event_source,
+        event_context,
+        event_type,
+        product,
+    )
+}
+
+/// Same as `product_event`, for writes whose resulting document is only
+/// known inside the repository's transaction.
+pub(crate) fn product_event_factory(
+    state: &AppState,
+    event_context: EventContext,
+    event_type: ProductEventType,
+) -> ProductOutboxFactory {
+    let topic = state.config.kafka_product_events_topic.clone();
+    let source = state.config.event_source.clone();
+    Box::new(move |product: &Product| {
+        product_event_entry(&topic, &source, &event_context, event_type, product)
+    })
+}
+
+/// Products may be changed by their owner or by an admin. Ownerless products
+/// predate ownership tracking and are left to admins.
+pub(crate) fn ensure_can_modify(product: &Product, caller: ObjectId, grants: &Grants) -> Result<(), AppError> {
+    if product.owner_id == Some(caller) || grants.allows(Scope::Admin) {
+        return Ok(());
+    }
+    warn!(
+        "User {} may not modify product {:?} owned by {:?}",
+        caller, product._id, product.owner_id
+    );
+    Err(AppError::Forbidden(
+        "Only the product's owner or an admin can modify it".to_string(),
+    ))
+}
 
 
 #[utoipa::path(
     post,
     path = "",
     tag = "product",
+    request_body = CreateProductRequest,
     responses(
-        (status = 201, description = "Create products successfully", body = [ProductResponse])
+        (status = 201, description = "Create products successfully", body = [ProductResponse],
+            headers(("ETag" = String, description = "Version of the product, for If-Match"))),
+        (status = 400, description = "Malformed request body", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Another product already has this SKU", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Body breaks validation rules", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
     ),
     security(
-        ("token" = [])
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
     )
 )]
 pub async fn create_product(
     State(state): State<AppState>,
-    Json(payload): Json<CreateProductRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let now = chrono::Utc::now();
-    let new_product = Product {
-        _id: Some(ObjectId::new()),
-        name: payload.name,
-        description: payload.description,
-        price: payload.price,
-        created_at: now,
-        updated_at: now,
-    };
-
-    let product_for_event = new_product.clone();
-
-    match state.db_repo.create_product(new_product).await {
-        Ok(inserted_id) => {
-            info!("Product created successfully with ID: {}", inserted_id);
-
-            let event = ProductEvent {
-                event_type: ProductEventType::Created,
-                product_id: inserted_id.to_hex(),
-                payload: Some(ProductResponse::from_product(&product_for_event)),
-                timestamp: chrono::Utc::now(),
-            };
-            send_kafka_event(
-                &state.kafka_producer,
-                &state.config.kafka_product_events_topic,
-                event,
-            )
-            .await;
+    user_id: UserId,
+    event_context: EventContext,
+    ValidatedJson(payload): ValidatedJson<CreateProductRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_sku_available(&state, payload.sku.as_deref(), None).await?;
+    let new_product = new_product(
+        payload,
+        user_id.object_id()?,
+        &state.config.default_currency,
+    )?;
+    let outbox_entry =
+        product_event(&state, &event_context, ProductEventType::Created, &new_product)?;
 
-            let response = ProductResponse::from_product(&product_for_event);
-            Ok((StatusCode::CREATED, Json(response)))
-        }
-        Err(e) => {
-            error!("Failed to create product in DB: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to create product".to_string(),
-            ))
-        }
-    }
+    let inserted_id = state.db_repo.create_product(new_product.clone(), outbox_entry).await?;
+    info!("Product created successfully with ID: {}", inserted_id);
+    Ok(product_with_etag(StatusCode::CREATED, &new_product))
 }
 
 #[utoipa::path(
@@ -82,45 +324,33 @@ pub async fn create_product(
     path = "/{id}",
     tag = "product",
     responses(
-        (status = 200, description = "Get product successfully", body = [ProductResponse])
+        (status = 200, description = "Get product successfully", body = [ProductResponse],
+            headers(("ETag" = String, description = "Version of the product, for If-Match"))),
+        (status = 400, description = "Invalid product ID", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Product not found", body = ProblemDetails, content_type = "application/problem+json")
     ),
     params(
         ("id" = String, Path, description = "product id")
     ),
     security(
-        ("token" = [])
+        ("token" = ["products:read"]),
+        ("api_key" = ["products:read"])
     )
 )]
 pub async fn get_product(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<(StatusCode, Json<ProductResponse>), (StatusCode, String)> {
-    let object_id = match ObjectId::from_str(&id) {
-        Ok(oid) => oid,
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                "Invalid product ID format".to_string(),
-            ));
-        }
-    };
+) -> Result<Response, AppError> {
+    let object_id = parse_object_id(&id, "product")?;
 
-    match state.db_repo.find_product_by_id(object_id).await {
-        Ok(Some(product)) => {
+    match state.db_repo.find_product_by_id(object_id).await? {
+        Some(product) => {
             info!("Product found: {}", id);
-            let response = ProductResponse::from_product(&product);
-            Ok((StatusCode::OK, Json(response)))
+            Ok(product_with_etag(StatusCode::OK, &product))
         }
-        Ok(None) => {
+        None => {
             warn!("Product not found: {}", id);
-            Err((StatusCode::NOT_FOUND, "Product not found".to_string()))
-        }
-        Err(e) => {
-            error!("Failed to fetch product {}: {:?}", id, e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to retrieve product".to_string(),
-            ))
+            Err(AppError::NotFound("Product not found".to_string()))
         }
     }
 }
@@ -130,176 +360,856 @@ pub async fn get_product(
     path = "",
     tag = "product",
     responses(
-        (status = 200, description = "List all products successfully", body = [ProductResponse])
+        (status = 200, description = "List products successfully", body = ProductPage),
+        (status = 400, description = "Invalid filter, limit or cursor", body = ProblemDetails, content_type = "application/problem+json")
     ),
+    params(ListProductsParams),
     security(
-        ("token" = [])
+        ("token" = ["products:read"]),
+        ("api_key" = ["products:read"])
     )
 )]
 pub async fn list_products(
     State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    match state.db_repo.find_all_products().await {
-        Ok(products) => {
-            info!("Retrieved {} products", products.len());
-            let response = products_to_responses(&products);
-            Ok((StatusCode::OK, Json(response)))
-        }
-        Err(e) => {
-            error!("Failed to list products: {:?}", e);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    user_id: UserId,
+    Query(params): Query<ListProductsParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = validate_limit(params.limit)?;
+    let owner_id = owner_filter(params.owner.as_deref(), &user_id)?;
+    let sort = params.sort.unwrap_or_default();
+    // Amounts in different currencies do not compare, so price bounds and
+    // sorting by price also restrict the list to a single currency.
+    let compares_prices = params.min_price.is_some()
+        || params.max_price.is_some()
+        || sort == ProductSortField::Price;
+    let currency = params
+        .currency
+        .as_deref()
+        .or(compares_prices.then_some(state.config.default_currency.as_str()))
+        .map(|code| {
+            Currency::from_code(code)
+                .ok_or_else(|| AppError::Validation(format!("Unsupported currency: {}", code)))
+        })
+        .transpose()?;
+    let bound = |name: &str, amount: Option<&str>| -> Result<Option<i64>, AppError> {
+        let (Some(amount), Some(currency)) = (amount, currency) else {
+            return Ok(None);
+        };
+        currency
+            .parse_amount(amount)
+            .map(Some)
+            .map_err(|e| AppError::Validation(format!("{} {}", name, e)))
+    };
+    let min_price_minor = bound("min_price", params.min_price.as_deref())?;
+    let max_price_minor = bound("max_price", params.max_price.as_deref())?;
+    if min_price_minor
+        .zip(max_price_minor)
+        .is_some_and(|(min, max)| min > max)
+    {
+        return Err(AppError::Validation(
+            "min_price cannot be greater than max_price".to_string(),
+        ));
+    }
+
+    let filter = ProductFilter {
+        min_price_minor,
+        max_price_minor,
+        currency: currency.map(|currency| currency.code.to_string()),
+        name_contains: params.name_contains,
+        created_after: params.created_after,
+        owner_id,
+        trashed: false,
+    };
+    let order = params.order.unwrap_or_default();
+    let page = find_page(&state, filter, sort, order, limit, params.cursor.as_deref()).await?;
+    info!("Retrieved {} products", page.items.len());
+    Ok((StatusCode::OK, Json(page)))
+}
+
+/// One page of products matching `filter`, continuing from `cursor`.
+async fn find_page(
+    state: &AppState,
+    filter: ProductFilter,
+    sort: ProductSortField,
+    order: SortOrder,
+    limit: u32,
+    cursor: Option<&str>,
+) -> Result<ProductPage, AppError> {
+    let after = match cursor.map(decode_cursor::<ProductCursor>) {
+        None => None,
+        Some(Some(cursor)) if cursor.sort == sort && cursor.order == order => Some(cursor),
+        Some(_) => {
+            return Err(AppError::BadRequest(
+                "Invalid cursor for this sort order".to_string(),
+            ));
         }
+    };
+
+    let query = ProductQuery {
+        filter,
+        sort,
+        order,
+        // One extra row tells us whether another page follows.
+        limit: limit as usize + 1,
+        after,
+    };
+
+    let mut products = state.db_repo.find_products(&query).await?;
+    let next_cursor = if products.len() > limit as usize {
+        products.truncate(limit as usize);
+        products.last().map(|last| {
+            encode_cursor(&ProductCursor {
+                sort,
+                order,
+                value: last.sort_value(sort),
+                id: last._id.expect("Product from DB must have an ID"),
+            })
+        })
+    } else {
+        None
+    };
+    Ok(ProductPage {
+        items: products_to_responses(&products),
+        next_cursor,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/trash",
+    tag = "product",
+    responses(
+        (status = 200, description = "Deleted products that can still be restored, most recently deleted first", body = ProductPage),
+        (status = 400, description = "Invalid limit or cursor", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(ListTrashParams),
+    security(
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
+    )
+)]
+pub async fn list_trash(
+    State(state): State<AppState>,
+    user_id: UserId,
+    Extension(grants): Extension<Grants>,
+    Query(params): Query<ListTrashParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = validate_limit(params.limit)?;
+    // Admins see the whole trash; everyone else only what they own.
+    let owner_id = if grants.allows(Scope::Admin) {
+        None
+    } else {
+        Some(user_id.object_id()?)
+    };
+    let filter = ProductFilter {
+        owner_id,
+        trashed: true,
+        ..ProductFilter::default()
+    };
+    // Deleting stamps `updated_at`, so this orders by deletion time.
+    let page = find_page(
+        &state,
+        filter,
+        ProductSortField::UpdatedAt,
+        SortOrder::Desc,
+        limit,
+        params.cursor.as_deref(),
+    )
+    .await?;
+    info!("Retrieved {} trashed products", page.items.len());
+    Ok((StatusCode::OK, Json(page)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "product",
+    responses(
+        (status = 200, description = "Search products successfully", body = ProductSearchPage),
+        (status = 400, description = "Missing search terms, invalid limit or cursor", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(SearchProductsParams),
+    security(
+        ("token" = ["products:read"]),
+        ("api_key" = ["products:read"])
+    )
+)]
+pub async fn search_products(
+    State(state): State<AppState>,
+    Query(params): Query<SearchProductsParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = validate_limit(params.limit)?;
+    let terms = search_terms(&params.q);
+    if terms.is_empty() {
+        return Err(AppError::Validation(
+            "q must contain at least one search term".to_string(),
+        ));
     }
+    let after = match params.cursor.as_deref().map(decode_cursor::<SearchCursor>) {
+        None => None,
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => return Err(AppError::BadRequest("Invalid cursor".to_string())),
+    };
+
+    let query = ProductSearchQuery {
+        text: params.q.trim().to_string(),
+        limit: limit as usize + 1,
+        after,
+    };
+
+    let mut hits = state.db_repo.search_products(&query).await?;
+    let next_cursor = if hits.len() > limit as usize {
+        hits.truncate(limit as usize);
+        hits.last().map(|last| {
+            encode_cursor(&SearchCursor {
+                score: last.score,
+                id: last.product._id.expect("Product from DB must have an ID"),
+            })
+        })
+    } else {
+        None
+    };
+    info!("Search for {:?} matched {} products", params.q, hits.len());
+    let items = hits
+        .iter()
+        .map(|hit| ProductSearchHit {
+            product: ProductResponse::from_product(&hit.product),
+            score: hit.score,
+            highlights: highlight_product(&hit.product, &terms),
+        })
+        .collect();
+    Ok((
+        StatusCode::OK,
+        Json(ProductSearchPage { items, next_cursor }),
+    ))
 }
 
 #[utoipa::path(
     patch,
     path = "/{id}",
     tag = "product",
+    request_body = UpdateProductRequest,
     responses(
-        (status = 200, description = "Update products successfully", body = [ProductResponse])
+        (status = 200, description = "Update products successfully", body = [ProductResponse],
+            headers(("ETag" = String, description = "New version of the product"))),
+        (status = 400, description = "Invalid product ID or body", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Caller neither owns the product nor is an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Body breaks validation rules", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Product not found", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Product changed during the update, or the SKU is taken", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 412, description = "Product is no longer at the version given in If-Match", body = ProblemDetails, content_type = "application/problem+json")
     ),
     params(
-        ("id" = String, Path, description = "product id")
+        ("id" = String, Path, description = "product id"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the version being edited; the update is refused if the product has changed since"),
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
     ),
     security(
-        ("token" = [])
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
     )
 )]
 pub async fn update_product(
     State(state): State<AppState>,
+    user_id: UserId,
+    Extension(grants): Extension<Grants>,
+    event_context: EventContext,
+    headers: HeaderMap,
     Path(id): Path<String>,
-    Json(payload): Json<UpdateProductRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let object_id = match ObjectId::from_str(&id) {
-        Ok(oid) => oid,
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                "Invalid product ID format".to_string(),
-            ));
-        }
+    ValidatedJson(payload): ValidatedJson<UpdateProductRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let object_id = parse_object_id(&id, "product")?;
+    let caller = user_id.object_id()?;
+    let if_match = headers
+        .get(header::IF_MATCH)
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| AppError::BadRequest("Invalid If-Match header".to_string()))
+        })
+        .transpose()?;
+    let Some(product) = state.db_repo.find_product_by_id(object_id).await? else {
+        warn!("Product not found for update: {}", id);
+        return Err(AppError::NotFound("Product not found".to_string()));
     };
+    ensure_can_modify(&product, caller, &grants)?;
+    check_if_match(if_match, &product)?;
+    ensure_sku_available(&state, payload.sku.as_deref(), Some(object_id)).await?;
 
-    let mut update_doc = Document::new();
-    
-    if let Some(name) = payload.name {
-        update_doc.insert("name", name);
+    let Some(update_doc) = update_document(payload, caller, &product.currency)? else {
+        return Ok(product_with_etag(StatusCode::OK, &product));
+    };
+
+    let event_factory = product_event_factory(&state, event_context, ProductEventType::Updated);
+
+    // Conditional on the version read above, so a concurrent writer is
+    // detected rather than overwritten.
+    if let Some(updated_product) = state
+        .db_repo
+        .update_product(object_id, product.version, update_doc, event_factory)
+        .await?
+    {
+        info!("Product updated successfully: {} (version {})", id, updated_product.version);
+        return Ok(product_with_etag(StatusCode::OK, &updated_product));
     }
-    if let Some(desc) = payload.description {
-        update_doc.insert("description", desc);
+    if state.db_repo.find_product_by_id(object_id).await?.is_none() {
+        warn!("Product not found for update: {}", id);
+        return Err(AppError::NotFound("Product not found".to_string()));
     }
-    if let Some(price) = payload.price {
-        update_doc.insert("price", price);
+    warn!("Product {} changed during update from version {}", id, product.version);
+    if if_match.is_some() {
+        Err(AppError::PreconditionFailed(
+            "Product has changed since the version given in If-Match".to_string(),
+        ))
+    } else {
+        Err(AppError::Conflict(
+            "Product was modified concurrently; fetch it and retry".to_string(),
+        ))
     }
+}
 
-    if update_doc.is_empty() {
-        return get_product(State(state), Path(id)).await;
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    tag = "product",
+    responses(
+        (status = 204, description = "Product moved to the trash"),
+        (status = 400, description = "Invalid product ID", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Caller neither owns the product nor is an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Product not found", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = String, Path, description = "product id"),
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
+    ),
+    security(
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
+    )
+)]
+pub async fn delete_product(
+    State(state): State<AppState>,
+    user_id: UserId,
+    Extension(grants): Extension<Grants>,
+    event_context: EventContext,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let object_id = parse_object_id(&id, "product")?;
+    let Some(product) = state.db_repo.find_product_by_id(object_id).await? else {
+        warn!("Product not found for deletion: {}", id);
+        return Err(AppError::NotFound("Product not found".to_string()));
+    };
+    let caller = user_id.object_id()?;
+    ensure_can_modify(&product, caller, &grants)?;
+
+    let outbox_entry = product_event(&state, &event_context, ProductEventType::Deleted, &product)?;
+
+    if state.db_repo.delete_product(object_id, caller, outbox_entry).await? {
+        info!("Product moved to trash: {}", id);
+        Ok((StatusCode::NO_CONTENT, ()))
+    } else {
+        warn!("Product not found for deletion: {}", id);
+        Err(AppError::NotFound("Product not found".to_string()))
     }
+}
 
-    update_doc.insert("updated_at", Bson::DateTime(DateTime::now()));
+#[utoipa::path(
+    post,
+    path = "/{id}/restore",
+    tag = "product",
+    responses(
+        (status = 200, description = "Product taken out of the trash", body = ProductResponse,
+            headers(("ETag" = String, description = "New version of the product"))),
+        (status = 400, description = "Invalid product ID", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Caller neither owns the product nor is an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such product in the trash", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = String, Path, description = "product id"),
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
+    ),
+    security(
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
+    )
+)]
+pub async fn restore_product(
+    State(state): State<AppState>,
+    user_id: UserId,
+    Extension(grants): Extension<Grants>,
+    event_context: EventContext,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let object_id = parse_object_id(&id, "product")?;
+    let not_in_trash = || AppError::NotFound("Product not found in trash".to_string());
+    let Some(product) = state.db_repo.find_trashed_product(object_id).await? else {
+        warn!("Product not found in trash: {}", id);
+        return Err(not_in_trash());
+    };
+    let caller = user_id.object_id()?;
+    ensure_can_modify(&product, caller, &grants)?;
 
-    match state.db_repo.update_product(object_id, update_doc).await {
-        Ok(true) => {
-            info!("Product updated successfully: {}", id);
-            match state.db_repo.find_product_by_id(object_id).await {
-                Ok(Some(updated_product)) => {
-                    let response = ProductResponse::from_product(&updated_product);
-                    let event = ProductEvent {
-                        event_type: ProductEventType::Updated,
-                        product_id: id.clone(),
-                        payload: Some(response.clone()),
-                        timestamp: chrono::Utc::now(),
-                    };
-                    send_kafka_event(
-                        &state.kafka_producer,
-                        &state.config.kafka_product_events_topic,
-                        event,
-                    )
-                    .await;
-                    Ok((StatusCode::OK, Json(response)))
-                }
-                Ok(None) => Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to retrieve updated product".to_string(),
-                )),
-                Err(_) => Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to retrieve updated product".to_string(),
-                )),
-            }
+    let event_factory = product_event_factory(&state, event_context, ProductEventType::Restored);
+
+    let Some(restored) = state
+        .db_repo
+        .restore_product(object_id, caller, event_factory)
+        .await?
+    else {
+        return Err(not_in_trash());
+    };
+    info!("Product restored from trash: {}", id);
+    Ok(product_with_etag(StatusCode::OK, &restored))
+}
+
+/// Bulk operation after parsing, before its product is looked up.
+enum BulkStep {
+    Create(CreateProductRequest),
+    Update {
+        id: ObjectId,
+        changes: UpdateProductRequest,
+        if_match: Option<String>,
+    },
+    Delete {
+        id: ObjectId,
+        if_match: Option<String>,
+    },
+}
+
+impl BulkStep {
+    fn target(&self) -> Option<ObjectId> {
+        match self {
+            BulkStep::Create(_) => None,
+            BulkStep::Update { id, .. } | BulkStep::Delete { id, .. } => Some(*id),
         }
-        Ok(false) => {
-            warn!("Product not found for update: {}", id);
-            Err((StatusCode::NOT_FOUND, "Product not found".to_string()))
+    }
+
+    /// SKU the step would give its product.
+    fn sku(&self) -> Option<&str> {
+        match self {
+            BulkStep::Create(request) => request.sku.as_deref(),
+            BulkStep::Update { changes, .. } => changes.sku.as_deref(),
+            BulkStep::Delete { .. } => None,
+        }
+    }
+}
+
+/// Parses and validates one entry on its own, so a bad entry only fails
+/// itself.
+fn parse_bulk_step(value: serde_json::Value) -> Result<BulkStep, AppError> {
+    let operation: BulkOperation = serde_json::from_value(value)
+        .map_err(|e| AppError::BadRequest(format!("Invalid operation: {}", e)))?;
+    Ok(match operation {
+        BulkOperation::Create { product } => {
+            product.validate()?;
+            BulkStep::Create(product)
         }
-        Err(e) => {
-            error!("Failed to update product {}: {:?}", id, e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to update product".to_string(),
-            ))
+        BulkOperation::Update {
+            id,
+            changes,
+            if_match,
+        } => {
+            changes.validate()?;
+            BulkStep::Update {
+                id: parse_object_id(&id, "product")?,
+                changes,
+                if_match,
+            }
         }
+        BulkOperation::Delete { id, if_match } => BulkStep::Delete {
+            id: parse_object_id(&id, "product")?,
+            if_match,
+        },
+    })
+}
+
+fn bulk_failure(index: usize, id: Option<ObjectId>, error: &AppError) -> BulkItemResult {
+    BulkItemResult {
+        index,
+        status: error.status().as_u16(),
+        id: id.map(|id| id.to_hex()),
+        product: None,
+        error: Some(error.to_problem()),
+    }
+}
+
+fn bulk_success(index: usize, status: StatusCode, product: &Product) -> BulkItemResult {
+    BulkItemResult {
+        index,
+        status: status.as_u16(),
+        id: product._id.map(|id| id.to_hex()),
+        product: (status != StatusCode::NO_CONTENT).then(|| ProductResponse::from_product(product)),
+        error: None,
     }
 }
 
 #[utoipa::path(
-    delete,
-    path = "/{id}",
+    post,
+    path = "/bulk",
     tag = "product",
+    request_body = BulkProductRequest,
     responses(
-        (status = 200, description = "Delete products successfully", body = [ProductResponse])
+        (status = 200, description = "Per-operation results; a failed operation does not stop the others", body = BulkProductResponse),
+        (status = 400, description = "Malformed request body", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "No operations, or more than 500", body = ProblemDetails, content_type = "application/problem+json")
     ),
     params(
-        ("id" = String, Path, description = "product id")
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
     ),
     security(
-        ("token" = [])
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
     )
 )]
-pub async fn delete_product(
+pub async fn bulk_products(
     State(state): State<AppState>,
-    Path(id): Path<String>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let object_id = match ObjectId::from_str(&id) {
-        Ok(oid) => oid,
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                "Invalid product ID format".to_string(),
-            ));
+    user_id: UserId,
+    Extension(grants): Extension<Grants>,
+    event_context: EventContext,
+    ValidatedJson(payload): ValidatedJson<BulkProductRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let caller = user_id.object_id()?;
+    let mut results = Vec::with_capacity(payload.operations.len());
+
+    // Each product may only be touched once per batch; otherwise the order of
+    // its events would depend on how the batch was split up. A SKU may only
+    // be claimed once, or the whole transaction would hit the unique index.
+    let mut steps = Vec::new();
+    let mut targets = HashSet::new();
+    let mut skus = HashSet::new();
+    for (index, value) in payload.operations.into_iter().enumerate() {
+        let step = match parse_bulk_step(value) {
+            Ok(step) => step,
+            Err(e) => {
+                results.push(bulk_failure(index, None, &e));
+                continue;
+            }
+        };
+        let target = step.target();
+        let duplicate = if target.is_some_and(|id| !targets.insert(id)) {
+            Some("Product appears more than once in this batch")
+        } else if step.sku().is_some_and(|sku| !skus.insert(sku.to_string())) {
+            Some("SKU appears more than once in this batch")
+        } else {
+            None
+        };
+        match duplicate {
+            Some(detail) => results.push(bulk_failure(
+                index,
+                target,
+                &AppError::BadRequest(detail.to_string()),
+            )),
+            None => steps.push((index, step)),
         }
-    };
+    }
 
-    match state.db_repo.delete_product(object_id).await {
-        Ok(true) => {
-            info!("Product deleted successfully: {}", id);
-
-            let event = ProductEvent::<()> {
-                event_type: ProductEventType::Deleted,
-                product_id: id.clone(),
-                payload: None,
-                timestamp: chrono::Utc::now(),
-            };
-            send_kafka_event(
-                &state.kafka_producer,
-                &state.config.kafka_product_events_topic,
-                event,
-            )
-            .await;
+    let targets: Vec<ObjectId> = targets.into_iter().collect();
+    let mut existing: HashMap<ObjectId, Product> = state
+        .db_repo
+        .find_products_by_ids(&targets)
+        .await?
+        .into_iter()
+        .filter_map(|product| Some((product._id?, product)))
+        .collect();
+    let skus: Vec<String> = skus.into_iter().collect();
+    let sku_holders: HashMap<String, Product> = state
+        .db_repo
+        .find_products_by_skus(&skus)
+        .await?
+        .into_iter()
+        .filter_map(|product| Some((product.sku.clone()?, product)))
+        .collect();
+    let mut take_existing = |id: ObjectId, if_match: Option<&str>| {
+        let product = existing
+            .remove(&id)
+            .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+        ensure_can_modify(&product, caller, &grants)?;
+        check_if_match(if_match, &product)?;
+        Ok::<_, AppError>(product)
+    };
 
-            Ok((StatusCode::NO_CONTENT, ()))
+    let mut writes = Vec::new();
+    let mut pending = Vec::new();
+    for (index, step) in steps {
+        let target = step.target();
+        let sku = step.sku().map(str::to_string);
+        let taken = step.sku().and_then(|sku| {
+            sku_holders
+                .get(sku)
+                .filter(|holder| holder._id != target)
+                .map(|holder| sku_conflict(sku, holder))
+        });
+        if let Some(e) = taken {
+            results.push(bulk_failure(index, target, &e));
+            continue;
         }
-        Ok(false) => {
-            warn!("Product not found for deletion: {}", id);
-            Err((StatusCode::NOT_FOUND, "Product not found".to_string()))
+        let planned = match step {
+            BulkStep::Create(request) => {
+                new_product(request, caller, &state.config.default_currency).and_then(|product| {
+                    let event =
+                        product_event(&state, &event_context, ProductEventType::Created, &product)?;
+                    Ok(Some((StatusCode::CREATED, ProductWrite::Create { product, event })))
+                })
+            }
+            BulkStep::Update {
+                id,
+                changes,
+                if_match,
+            } => take_existing(id, if_match.as_deref()).and_then(|product| {
+                Ok(match update_document(changes, caller, &product.currency)? {
+                    None => {
+                        results.push(bulk_success(index, StatusCode::OK, &product));
+                        None
+                    }
+                    Some(update) => Some((
+                        StatusCode::OK,
+                        ProductWrite::Update {
+                            id,
+                            expected_version: product.version,
+                            update,
+                            event: product_event_factory(
+                                &state,
+                                event_context.clone(),
+                                ProductEventType::Updated,
+                            ),
+                        },
+                    )),
+                })
+            }),
+            BulkStep::Delete { id, if_match } => take_existing(id, if_match.as_deref())
+                .and_then(|product| {
+                    let event =
+                        product_event(&state, &event_context, ProductEventType::Deleted, &product)?;
+                    Ok(Some((
+                        StatusCode::NO_CONTENT,
+                        ProductWrite::Delete {
+                            id,
+                            expected_version: product.version,
+                            deleted_by: caller,
+                            event,
+                        },
+                    )))
+                }),
+        };
+        match planned {
+            Ok(Some((status, write))) => {
+                pending.push((index, target, sku, status));
+                writes.push(write);
+            }
+            Ok(None) => {}
+            Err(e) => results.push(bulk_failure(index, target, &e)),
         }
-        Err(e) => {
-            error!("Failed to delete product {}: {:?}", id, e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to delete product".to_string(),
-            ))
+    }
+
+    if !writes.is_empty() {
+        let outcomes = state.db_repo.write_products(writes).await?;
+        for ((index, target, sku, status), outcome) in pending.into_iter().zip(outcomes) {
+            results.push(match outcome {
+                ProductWriteOutcome::Written(product) => bulk_success(index, status, &product),
+                ProductWriteOutcome::Stale => bulk_failure(
+                    index,
+                    target,
+                    &AppError::Conflict(
+                        "Product was modified concurrently; fetch it and retry".to_string(),
+                    ),
+                ),
+                ProductWriteOutcome::SkuTaken => {
+                    bulk_failure(index, target, &sku_taken_meanwhile(sku.as_deref()))
+                }
+            });
         }
     }
+
+    results.sort_by_key(|result| result.index);
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    let succeeded = results.len() - failed;
+    info!("Bulk product request: {} succeeded, {} failed", succeeded, failed);
+    Ok((
+        StatusCode::OK,
+        Json(BulkProductResponse {
+            succeeded,
+            failed,
+            results,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "product",
+    responses(
+        (status = 200, description = "Every live product, streamed in `_id` order",
+            content(
+                ("text/csv"),
+                (ProductResponse = "application/x-ndjson")
+            ),
+            headers(("Content-Disposition" = String, description = "Suggested file name"))),
+        (status = 400, description = "Invalid format or owner", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(ExportProductsParams),
+    security(
+        ("token" = ["products:read"]),
+        ("api_key" = ["products:read"])
+    )
+)]
+pub async fn export_products(
+    State(state): State<AppState>,
+    user_id: UserId,
+    Query(params): Query<ExportProductsParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let format = params.format.unwrap_or_default();
+    let filter = ProductFilter {
+        owner_id: owner_filter(params.owner.as_deref(), &user_id)?,
+        ..ProductFilter::default()
+    };
+    let products = state.db_repo.stream_products(&filter).await?;
+
+    // Headers are already sent once the body streams, so a failure part way
+    // can only cut the file short; it is logged for the operator.
+    let header = match format {
+        ProductFileFormat::Csv => Some(Ok(product_csv_header())),
+        ProductFileFormat::Jsonl => None,
+    };
+    let rows = products
+        .map_ok(move |product| match format {
+            ProductFileFormat::Csv => product_csv_record(&product),
+            ProductFileFormat::Jsonl => product_json_line(&product),
+        })
+        .inspect_err(|e| error!("Product export aborted: {:?}", e));
+    let body = Body::from_stream(stream::iter(header).chain(rows));
+
+    info!("Streaming product export as {:?}", format);
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"products.{}\"", format.extension()),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/import",
+    tag = "product",
+    request_body(
+        description = "CSV with a header row, or JSON Lines. Rows need `sku`, `name` and `price`; `description` and `currency` are optional and other columns are ignored, so an export can be uploaded again.",
+        content(("text/csv"), (CreateProductRequest = "application/x-ndjson"))
+    ),
+    responses(
+        (status = 202, description = "Import queued; poll the job for progress and the row report", body = ImportJobResponse,
+            headers(("Location" = String, description = "URL of the import job"))),
+        (status = 400, description = "Empty upload or unknown format", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 413, description = "Upload larger than PRODUCT_IMPORT_MAX_BYTES", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ImportProductsParams,
+        ("x-correlation-id" = Option<String>, Header, description = "correlation id propagated to emitted events"),
+        ("x-causation-id" = Option<String>, Header, description = "id of the message that caused this request")
+    ),
+    security(
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
+    )
+)]
+pub async fn import_products(
+    State(state): State<AppState>,
+    user_id: UserId,
+    Extension(grants): Extension<Grants>,
+    event_context: EventContext,
+    headers: HeaderMap,
+    Query(params): Query<ImportProductsParams>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let format = params
+        .format
+        .or_else(|| {
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(ProductFileFormat::from_content_type)
+        })
+        .ok_or_else(|| {
+            AppError::BadRequest(
+                "Pass format=csv or format=jsonl, or upload as text/csv or application/x-ndjson"
+                    .to_string(),
+            )
+        })?;
+    if body.is_empty() {
+        return Err(AppError::BadRequest("The uploaded file is empty".to_string()));
+    }
+    let caller = user_id.object_id()?;
+
+    let mut job = ImportJob {
+        _id: None,
+        user_id: caller,
+        format,
+        status: ImportStatus::Pending,
+        total_rows: 0,
+        processed_rows: 0,
+        created: 0,
+        updated: 0,
+        unchanged: 0,
+        failed: 0,
+        errors: Vec::new(),
+        errors_truncated: false,
+        error: None,
+        created_at: chrono::Utc::now(),
+        finished_at: None,
+    };
+    let id = state.db_repo.create_import_job(job.clone()).await?;
+    job._id = Some(id);
+    info!("Product import {} queued: {} bytes of {:?}", id, body.len(), format);
+
+    let response = ImportJobResponse::from_job(&job);
+    ProductImporter::new(state, caller, grants, event_context).spawn(job, body);
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/products/import/{}", id))],
+        Json(response),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/import/{id}",
+    tag = "product",
+    responses(
+        (status = 200, description = "Progress of the import, with failed rows", body = ImportJobResponse),
+        (status = 400, description = "Invalid job ID", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such import job for the caller", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = String, Path, description = "import job id")
+    ),
+    security(
+        ("token" = ["products:write"]),
+        ("api_key" = ["products:write"])
+    )
+)]
+pub async fn get_import_job(
+    State(state): State<AppState>,
+    user_id: UserId,
+    Extension(grants): Extension<Grants>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let object_id = parse_object_id(&id, "import job")?;
+    let caller = user_id.object_id()?;
+    // Other users' jobs read as missing rather than forbidden.
+    let job = state
+        .db_repo
+        .find_import_job(object_id)
+        .await?
+        .filter(|job| job.user_id == caller || grants.allows(Scope::Admin))
+        .ok_or_else(|| AppError::NotFound("Import job not found".to_string()))?;
+    Ok((StatusCode::OK, Json(ImportJobResponse::from_job(&job))))
 }
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use axum::body::Bytes;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use tokio::task::JoinHandle;
+use validator::{Validate, ValidationError};
+
+use crate::{
+    auth::models::Grants,
+    error::AppError,
+    kafka::{envelope::EventContext, producer::ProductEventType},
+    products::{
+        handlers::{
+            ensure_can_modify, field_error, new_product, price_in, product_event,
+            product_event_factory, sku_conflict, sku_taken_meanwhile, update_document,
+        },
+        models::{
+            CreateProductRequest, ImportJob, ImportRowError, ImportStatus, Product,
+            ProductFileFormat, ProductWrite, ProductWriteOutcome, UpdateProductRequest,
+        },
+    },
+    state::AppState,
+};
+
+/// Rows written per transaction; also how often progress is saved.
+const IMPORT_BATCH_SIZE: usize = 500;
+/// Row errors kept on the job, so a hopeless file cannot bloat it.
+const MAX_REPORTED_ERRORS: usize = 1000;
+
+/// A data row of an upload and the line it starts on.
+struct ImportRow {
+    line: u64,
+    product: Result<CreateProductRequest, AppError>,
+}
+
+/// Upserts the rows of an uploaded file by SKU, recording progress and
+/// row-level failures on its `ImportJob`. Rows go through the same checks
+/// and events as `POST /products/bulk`.
+pub struct ProductImporter {
+    state: AppState,
+    caller: ObjectId,
+    grants: Grants,
+    event_context: EventContext,
+}
+
+impl ProductImporter {
+    pub fn new(
+        state: AppState,
+        caller: ObjectId,
+        grants: Grants,
+        event_context: EventContext,
+    ) -> Self {
+        Self {
+            state,
+            caller,
+            grants,
+            event_context,
+        }
+    }
+
+    /// Runs the import in the background. The job lives in this process only
+    /// while running: if the process stops, the job stays `running`.
+    pub fn spawn(self, mut job: ImportJob, data: Bytes) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Err(e) = self.run(&mut job, &data).await {
+                tracing::error!("Product import {:?} failed: {:?}", job._id, e);
+                job.status = ImportStatus::Failed;
+                job.error = Some(e.to_string());
+                job.finished_at = Some(Utc::now());
+                if let Err(e) = self.state.db_repo.save_import_job(&job).await {
+                    tracing::error!("Failed to record failed import {:?}: {:?}", job._id, e);
+                }
+            }
+        })
+    }
+
+    async fn run(&self, job: &mut ImportJob, data: &[u8]) -> Result<(), AppError> {
+        job.status = ImportStatus::Running;
+        self.state.db_repo.save_import_job(job).await?;
+
+        let rows = parse_rows(job.format, data).map_err(AppError::BadRequest)?;
+        job.total_rows = rows.len() as u64;
+
+        // A SKU names one product, so a file listing it twice is ambiguous;
+        // the first row wins.
+        let mut first_lines: HashMap<String, u64> = HashMap::new();
+        let mut valid = Vec::with_capacity(rows.len());
+        for row in rows {
+            let sku = row.product.as_ref().ok().and_then(|product| product.sku.clone());
+            let checked = row.product.and_then(|product| {
+                let sku = required_sku(&product)?.to_string();
+                if let Some(first) = first_lines.get(&sku) {
+                    return Err(AppError::BadRequest(format!(
+                        "SKU {} already appeared on line {}",
+                        sku, first
+                    )));
+                }
+                first_lines.insert(sku, row.line);
+                Ok(product)
+            });
+            match checked {
+                Ok(product) => valid.push((row.line, product)),
+                Err(e) => record_failure(job, row.line, sku, &e),
+            }
+        }
+        self.state.db_repo.save_import_job(job).await?;
+
+        let mut rows = valid.into_iter().peekable();
+        while rows.peek().is_some() {
+            let batch: Vec<_> = rows.by_ref().take(IMPORT_BATCH_SIZE).collect();
+            self.import_batch(job, batch).await?;
+            self.state.db_repo.save_import_job(job).await?;
+        }
+
+        job.errors.sort_by_key(|error| error.line);
+        job.status = ImportStatus::Completed;
+        job.finished_at = Some(Utc::now());
+        self.state.db_repo.save_import_job(job).await?;
+        tracing::info!(
+            "Product import {:?} completed: {} created, {} updated, {} unchanged, {} failed",
+            job._id,
+            job.created,
+            job.updated,
+            job.unchanged,
+            job.failed
+        );
+        Ok(())
+    }
+
+    async fn import_batch(
+        &self,
+        job: &mut ImportJob,
+        batch: Vec<(u64, CreateProductRequest)>,
+    ) -> Result<(), AppError> {
+        let skus: Vec<String> = batch
+            .iter()
+            .filter_map(|(_, product)| product.sku.clone())
+            .collect();
+        let existing: HashMap<String, Product> = self
+            .state
+            .db_repo
+            .find_products_by_skus(&skus)
+            .await?
+            .into_iter()
+            .filter_map(|product| Some((product.sku.clone()?, product)))
+            .collect();
+
+        let mut writes = Vec::new();
+        let mut pending = Vec::new();
+        for (line, request) in batch {
+            let sku = request.sku.clone();
+            match self.plan_row(existing.get(sku.as_deref().unwrap_or_default()), request) {
+                Ok(Some(write)) => {
+                    pending.push((line, sku));
+                    writes.push(write);
+                }
+                Ok(None) => {
+                    job.unchanged += 1;
+                    job.processed_rows += 1;
+                }
+                Err(e) => record_failure(job, line, sku, &e),
+            }
+        }
+        if writes.is_empty() {
+            return Ok(());
+        }
+
+        let is_create: Vec<bool> = writes
+            .iter()
+            .map(|write| matches!(write, ProductWrite::Create { .. }))
+            .collect();
+        match self.state.db_repo.write_products(writes).await {
+            Ok(outcomes) => {
+                for (((line, sku), created), outcome) in
+                    pending.into_iter().zip(is_create).zip(outcomes)
+                {
+                    match (outcome, created) {
+                        (ProductWriteOutcome::Written(_), true) => job.created += 1,
+                        (ProductWriteOutcome::Written(_), false) => job.updated += 1,
+                        (ProductWriteOutcome::Stale, _) => {
+                            record_failure(
+                                job,
+                                line,
+                                sku,
+                                &AppError::Conflict(
+                                    "Product was modified concurrently".to_string(),
+                                ),
+                            );
+                            continue;
+                        }
+                        (ProductWriteOutcome::SkuTaken, _) => {
+                            let e = sku_taken_meanwhile(sku.as_deref());
+                            record_failure(job, line, sku, &e);
+                            continue;
+                        }
+                    }
+                    job.processed_rows += 1;
+                }
+            }
+            Err(e) => {
+                let e = AppError::from(e);
+                for (line, sku) in pending {
+                    record_failure(job, line, sku, &e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The write that brings the product for `request` in line with it, or
+    /// `None` if it already matches.
+    fn plan_row(
+        &self,
+        current: Option<&Product>,
+        request: CreateProductRequest,
+    ) -> Result<Option<ProductWrite>, AppError> {
+        let Some(current) = current else {
+            let product = new_product(request, self.caller, &self.state.config.default_currency)?;
+            let event = product_event(
+                &self.state,
+                &self.event_context,
+                ProductEventType::Created,
+                &product,
+            )?;
+            return Ok(Some(ProductWrite::Create { product, event }));
+        };
+        if current.deleted_at.is_some() {
+            return Err(sku_conflict(
+                request.sku.as_deref().unwrap_or_default(),
+                current,
+            ));
+        }
+        ensure_can_modify(current, self.caller, &self.grants)?;
+
+        // Prices compare as amounts, so "19.9" matches a stored 19.90.
+        let currency = request.currency.as_deref().unwrap_or(&current.currency);
+        let (price_minor, currency) = price_in(&request.price, currency)?;
+        let price_changed = price_minor != current.price_minor || currency.code != current.currency;
+        let changes = UpdateProductRequest {
+            sku: None,
+            name: (request.name != current.name).then_some(request.name),
+            description: (request.description != current.description)
+                .then_some(request.description),
+            price: price_changed.then_some(request.price),
+            currency: price_changed.then(|| currency.code.to_string()),
+        };
+        let update = update_document(changes, self.caller, &current.currency)?;
+        Ok(update.map(|update| ProductWrite::Update {
+            id: current._id.expect("Product from DB must have an ID"),
+            expected_version: current.version,
+            update,
+            event: product_event_factory(
+                &self.state,
+                self.event_context.clone(),
+                ProductEventType::Updated,
+            ),
+        }))
+    }
+}
+
+fn record_failure(job: &mut ImportJob, line: u64, sku: Option<String>, error: &AppError) {
+    job.failed += 1;
+    job.processed_rows += 1;
+    if job.errors.len() < MAX_REPORTED_ERRORS {
+        job.errors.push(ImportRowError {
+            line,
+            sku,
+            error: error.to_problem(),
+        });
+    } else {
+        job.errors_truncated = true;
+    }
+}
+
+/// Validates a row like a create request; rows must also carry the SKU they
+/// are matched on.
+fn required_sku(product: &CreateProductRequest) -> Result<&str, AppError> {
+    product.validate()?;
+    product.sku.as_deref().ok_or_else(|| {
+        field_error(
+            "sku",
+            ValidationError::new("required").with_message("is required for imports".into()),
+        )
+    })
+}
+
+/// Splits an upload into rows. Only a file that cannot be read at all is an
+/// error; a malformed row fails on its own.
+fn parse_rows(format: ProductFileFormat, data: &[u8]) -> Result<Vec<ImportRow>, String> {
+    match format {
+        ProductFileFormat::Csv => parse_csv(data),
+        ProductFileFormat::Jsonl => Ok(parse_jsonl(data)),
+    }
+}
+
+fn parse_csv(data: &[u8]) -> Result<Vec<ImportRow>, String> {
+    // Spreadsheets tend to drop trailing empty cells, so short rows are
+    // accepted and their missing columns treated as empty.
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Could not read the CSV header: {}", e))?
+        .clone();
+    let missing: Vec<&str> = ["sku", "name", "price"]
+        .into_iter()
+        .filter(|column| !headers.iter().any(|header| header == *column))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "CSV header is missing the {} column(s)",
+            missing.join(", ")
+        ));
+    }
+
+    Ok(reader
+        .records()
+        .map(|record| match record {
+            Ok(mut record) => {
+                while record.len() < headers.len() {
+                    record.push_field("");
+                }
+                ImportRow {
+                    line: record.position().map_or(0, |position| position.line()),
+                    product: record
+                        .deserialize(Some(&headers))
+                        .map_err(|e| AppError::BadRequest(format!("Invalid row: {}", e))),
+                }
+            }
+            Err(e) => ImportRow {
+                line: e.position().map_or(0, |position| position.line()),
+                product: Err(AppError::BadRequest(format!("Invalid row: {}", e))),
+            },
+        })
+        .collect())
+}
+
+fn parse_jsonl(data: &[u8]) -> Vec<ImportRow> {
+    data.split(|byte| *byte == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.trim_ascii().is_empty())
+        .map(|(index, line)| ImportRow {
+            line: index as u64 + 1,
+            product: serde_json::from_slice(line)
+                .map_err(|e| AppError::BadRequest(format!("Invalid row: {}", e))),
+        })
+        .collect()
+}
@@ -1,3 +1,6 @@
 pub mod models;
 pub mod handlers;
+pub mod import;
+pub mod money;
+pub mod purge;
 pub mod utils;
\ No newline at end of file
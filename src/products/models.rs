@@ -1,59 +1,574 @@
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{Bson, Document, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::{
+    error::ProblemDetails,
+    outbox::models::{OutboxEntry, ProductOutboxFactory},
+};
+
+use super::{
+    money::{deserialize_price, format_price},
+    utils::{validate_currency, validate_not_blank, validate_price, validate_sku},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Product {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub _id: Option<ObjectId>,
+    /// Merchant-assigned stock keeping unit, unique across live and trashed
+    /// products. Imports match existing products on it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
     pub name: String,
     pub description: String,
-    pub price: f64,
+    /// Price in minor units of `currency` (cents for USD), so it is exact.
+    pub price_minor: i64,
+    /// ISO 4217 code, upper case
+    pub currency: String,
+    /// Absent on products created before ownership was tracked; only admins
+    /// may change those.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<ObjectId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<ObjectId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<ObjectId>,
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub created_at: DateTime<Utc>,
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub updated_at: DateTime<Utc>,
+    /// Bumped by every update; exposed as the `ETag`.
+    #[serde(default)]
+    pub version: i64,
+    /// Set while the product is in the trash. Trashed products are hidden
+    /// from normal reads and purged once the retention period has passed.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<ObjectId>,
 }
 
-#[derive(Deserialize, Debug, ToSchema)]
+impl Product {
+    /// Value of `field` as stored in MongoDB, used for keyset pagination.
+    pub fn sort_value(&self, field: ProductSortField) -> Bson {
+        match field {
+            ProductSortField::Name => Bson::String(self.name.clone()),
+            ProductSortField::Price => Bson::Int64(self.price_minor),
+            ProductSortField::CreatedAt => Bson::DateTime(self.created_at.into()),
+            ProductSortField::UpdatedAt => Bson::DateTime(self.updated_at.into()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, ToSchema, Validate)]
 pub struct CreateProductRequest {
+    /// Letters, digits, `.`, `_` and `-`; must not be used by another product
+    #[schema(min_length = 1, max_length = 64)]
+    #[validate(custom(function = "validate_sku"))]
+    pub sku: Option<String>,
+    #[schema(min_length = 1, max_length = 200)]
+    #[validate(
+        length(min = 1, max = 200, message = "must be between 1 and 200 characters"),
+        custom(function = "validate_not_blank")
+    )]
     pub name: String,
     #[serde(default)]
+    #[schema(max_length = 2000)]
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
     pub description: String,
-    pub price: f64,
+    /// Decimal amount in `currency`, e.g. `"19.99"`
+    #[schema(example = "19.99")]
+    #[validate(custom(function = "validate_price"))]
+    pub price: String,
+    /// ISO 4217 code; the service's default currency when absent
+    #[schema(example = "USD", min_length = 3, max_length = 3)]
+    #[validate(custom(function = "validate_currency"))]
+    pub currency: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Default, ToSchema)]
+#[derive(Deserialize, Debug, Default, ToSchema, Validate)]
 pub struct UpdateProductRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(min_length = 1, max_length = 64)]
+    #[validate(custom(function = "validate_sku"))]
+    pub sku: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(min_length = 1, max_length = 200)]
+    #[validate(
+        length(min = 1, max = 200, message = "must be between 1 and 200 characters"),
+        custom(function = "validate_not_blank")
+    )]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(max_length = 2000)]
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
     pub description: Option<String>,
+    /// Decimal amount in `currency`, or in the current currency if that is
+    /// not changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "19.99")]
+    #[validate(custom(function = "validate_price"))]
+    pub price: Option<String>,
+    /// ISO 4217 code; can only change together with `price`
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub price: Option<f64>,
+    #[schema(example = "USD", min_length = 3, max_length = 3)]
+    #[validate(custom(function = "validate_currency"))]
+    pub currency: Option<String>,
 }
 
 
 #[derive(Serialize, Debug, Clone, Deserialize, ToSchema)]
 pub struct ProductResponse {
     pub id: String,
+    pub sku: Option<String>,
     pub name: String,
     pub description: String,
-    pub price: f64,
+    /// Exact decimal amount in `currency`. Product events before schema
+    /// version 7 carried a JSON number here, and no currency.
+    #[serde(deserialize_with = "deserialize_price")]
+    #[schema(example = "19.99")]
+    pub price: String,
+    #[serde(default)]
+    #[schema(example = "USD")]
+    pub currency: String,
+    /// User who owns the product; only they or an admin may change it
+    pub owner_id: Option<String>,
+    pub created_by: Option<String>,
+    /// User who last changed the product
+    pub updated_by: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// Changes with every update; also sent as the `ETag` header. Added to
+    /// product events in schema version 4.
+    #[serde(default)]
+    pub version: i64,
+    /// Only set on products in the trash. Added to product events in schema
+    /// version 5.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
 }
 
 impl ProductResponse {
     pub fn from_product(product: &Product) -> Self {
         ProductResponse {
             id: product._id.expect("Product from DB must have an ID").to_hex(),
+            sku: product.sku.clone(),
             name: product.name.clone(),
             description: product.description.clone(),
-            price: product.price,
+            price: format_price(product.price_minor, &product.currency),
+            currency: product.currency.clone(),
+            owner_id: product.owner_id.map(|id| id.to_hex()),
+            created_by: product.created_by.map(|id| id.to_hex()),
+            updated_by: product.updated_by.map(|id| id.to_hex()),
             created_at: product.created_at.to_string(),
             updated_at: product.updated_at.to_string(),
+            version: product.version,
+            deleted_at: product.deleted_at.map(|at| at.to_string()),
+            deleted_by: product.deleted_by.map(|id| id.to_hex()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductSortField {
+    Name,
+    Price,
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl ProductSortField {
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            ProductSortField::Name => "name",
+            ProductSortField::Price => "price_minor",
+            ProductSortField::CreatedAt => "created_at",
+            ProductSortField::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListProductsParams {
+    /// Page size, between 1 and 100 (default 20)
+    pub limit: Option<u32>,
+    /// Opaque cursor returned as `next_cursor` by the previous page
+    pub cursor: Option<String>,
+    /// Field to sort by (default `created_at`). Sorting by `price` lists one
+    /// currency only, see `currency`.
+    pub sort: Option<ProductSortField>,
+    /// Sort direction (default `asc`)
+    pub order: Option<SortOrder>,
+    /// Decimal amount in `currency`
+    pub min_price: Option<String>,
+    /// Decimal amount in `currency`
+    pub max_price: Option<String>,
+    /// Only products priced in this ISO 4217 currency. Price bounds and
+    /// sorting by price always apply within one currency, this one or the
+    /// service's default.
+    pub currency: Option<String>,
+    /// Case-insensitive substring match on the product name
+    pub name_contains: Option<String>,
+    /// Only products created strictly after this RFC 3339 timestamp
+    #[param(value_type = Option<String>, format = DateTime)]
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only products owned by this user id, or by the caller with `me`
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProductFilter {
+    /// Bounds in minor units of `currency`
+    pub min_price_minor: Option<i64>,
+    pub max_price_minor: Option<i64>,
+    pub currency: Option<String>,
+    pub name_contains: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub owner_id: Option<ObjectId>,
+    /// List the trash instead of live products.
+    pub trashed: bool,
+}
+
+/// Position after which the next page starts: the sort value and `_id` of the
+/// last product on the previous page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProductCursor {
+    pub sort: ProductSortField,
+    pub order: SortOrder,
+    pub value: Bson,
+    pub id: ObjectId,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProductQuery {
+    pub filter: ProductFilter,
+    pub sort: ProductSortField,
+    pub order: SortOrder,
+    pub limit: usize,
+    pub after: Option<ProductCursor>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ProductPage {
+    pub items: Vec<ProductResponse>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListTrashParams {
+    /// Page size, between 1 and 100 (default 20)
+    pub limit: Option<u32>,
+    /// Opaque cursor returned as `next_cursor` by the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchProductsParams {
+    /// Search terms matched against product name and description
+    pub q: String,
+    /// Page size, between 1 and 100 (default 20)
+    pub limit: Option<u32>,
+    /// Opaque cursor returned as `next_cursor` by the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchCursor {
+    pub score: f64,
+    pub id: ObjectId,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProductSearchQuery {
+    pub text: String,
+    pub limit: usize,
+    pub after: Option<SearchCursor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScoredProduct {
+    pub product: Product,
+    pub score: f64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct SearchHighlight {
+    pub field: String,
+    /// Excerpt around the first match, with matched terms wrapped in `<em>`
+    pub snippet: String,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ProductSearchHit {
+    #[serde(flatten)]
+    pub product: ProductResponse,
+    pub score: f64,
+    pub highlights: Vec<SearchHighlight>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ProductSearchPage {
+    pub items: Vec<ProductSearchHit>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// One entry of `POST /products/bulk`.
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Create {
+        product: CreateProductRequest,
+    },
+    Update {
+        id: String,
+        changes: UpdateProductRequest,
+        /// Same as the `If-Match` header of a single update
+        if_match: Option<String>,
+    },
+    /// Moves the product to the trash
+    Delete {
+        id: String,
+        if_match: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Debug, ToSchema, Validate)]
+pub struct BulkProductRequest {
+    /// Applied in order. Each entry is checked on its own, so a malformed one
+    /// fails alone instead of rejecting the batch.
+    #[schema(value_type = Vec<BulkOperation>, min_items = 1, max_items = 500)]
+    #[validate(length(min = 1, max = 500, message = "must contain between 1 and 500 operations"))]
+    pub operations: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BulkItemResult {
+    /// Position of the operation in the request
+    pub index: usize,
+    /// HTTP status the operation would have had on its own
+    pub status: u16,
+    /// Product the operation applied to, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Product after a successful create or update
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<ProductResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ProblemDetails>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BulkProductResponse {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// One result per operation, in request order
+    pub results: Vec<BulkItemResult>,
+}
+
+/// A write that passed its checks in a bulk request, with its event. Updates
+/// and deletes only apply if the product is still live and at
+/// `expected_version`.
+pub enum ProductWrite {
+    Create {
+        product: Product,
+        event: OutboxEntry,
+    },
+    Update {
+        id: ObjectId,
+        expected_version: i64,
+        update: Document,
+        event: ProductOutboxFactory,
+    },
+    Delete {
+        id: ObjectId,
+        expected_version: i64,
+        deleted_by: ObjectId,
+        event: OutboxEntry,
+    },
+}
+
+/// What became of a `ProductWrite`.
+#[derive(Debug)]
+pub enum ProductWriteOutcome {
+    /// The product as written
+    Written(Box<Product>),
+    /// The product changed or went away since it was read
+    Stale,
+    /// Another product took the SKU since it was checked
+    SkuTaken,
+}
+
+/// File formats accepted by `/products/import` and produced by
+/// `/products/export`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProductFileFormat {
+    /// Comma-separated values with a header row
+    #[default]
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl ProductFileFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ProductFileFormat::Csv => "text/csv; charset=utf-8",
+            ProductFileFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ProductFileFormat::Csv => "csv",
+            ProductFileFormat::Jsonl => "jsonl",
+        }
+    }
+
+    /// Format named by an upload's `Content-Type`, ignoring parameters.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        match essence.to_ascii_lowercase().as_str() {
+            "text/csv" => Some(ProductFileFormat::Csv),
+            "application/x-ndjson" | "application/jsonl" | "application/jsonlines" => {
+                Some(ProductFileFormat::Jsonl)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportProductsParams {
+    /// Output format (default `csv`)
+    pub format: Option<ProductFileFormat>,
+    /// Only products owned by this user id, or by the caller with `me`
+    pub owner: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportProductsParams {
+    /// Format of the upload; taken from `Content-Type` when absent
+    pub format: Option<ProductFileFormat>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Pending,
+    Running,
+    Completed,
+    /// The file could not be read at all; see `error`
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ImportRowError {
+    /// Line of the uploaded file the row starts on
+    pub line: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    pub error: ProblemDetails,
+}
+
+/// Progress and outcome of a product import. Rows are upserted by SKU in
+/// batches, each with its own transaction, so an import that fails part way
+/// keeps the batches written before.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportJob {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub format: ProductFileFormat,
+    pub status: ImportStatus,
+    pub total_rows: u64,
+    pub processed_rows: u64,
+    pub created: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+    pub failed: u64,
+    /// First failures only; `errors_truncated` is set once the cap is hit.
+    pub errors: Vec<ImportRowError>,
+    pub errors_truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ImportJobResponse {
+    pub id: String,
+    pub status: ImportStatus,
+    pub format: ProductFileFormat,
+    /// Data rows in the file, known once it has been parsed
+    pub total_rows: u64,
+    pub processed_rows: u64,
+    pub created: u64,
+    pub updated: u64,
+    /// Rows that matched an existing product without changing it
+    pub unchanged: u64,
+    pub failed: u64,
+    pub errors: Vec<ImportRowError>,
+    /// More rows failed than are listed in `errors`
+    pub errors_truncated: bool,
+    /// Why the whole import failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+}
+
+impl ImportJobResponse {
+    pub fn from_job(job: &ImportJob) -> Self {
+        ImportJobResponse {
+            id: job._id.expect("Import job from DB must have an ID").to_hex(),
+            status: job.status,
+            format: job.format,
+            total_rows: job.total_rows,
+            processed_rows: job.processed_rows,
+            created: job.created,
+            updated: job.updated,
+            unchanged: job.unchanged,
+            failed: job.failed,
+            errors: job.errors.clone(),
+            errors_truncated: job.errors_truncated,
+            error: job.error.clone(),
+            created_at: job.created_at.to_string(),
+            finished_at: job.finished_at.map(|at| at.to_string()),
         }
     }
-}
\ No newline at end of file
+}
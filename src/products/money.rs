@@ -0,0 +1,238 @@
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+/// Largest price accepted, in major units of any currency.
+const MAX_PRICE_MAJOR: u64 = 1_000_000_000;
+/// Most decimal places any supported currency has.
+pub const MAX_EXPONENT: u32 = 3;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AmountError {
+    #[error("must be a decimal amount such as \"19.99\"")]
+    Format,
+    #[error("must have at most {0} decimal places")]
+    Precision(u32),
+    #[error("must be between 0 and 1000000000")]
+    Range,
+}
+
+impl AmountError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AmountError::Format => "format",
+            AmountError::Precision(_) => "precision",
+            AmountError::Range => "range",
+        }
+    }
+}
+
+/// An ISO 4217 currency and how many digits its minor unit has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Currency {
+    pub code: &'static str,
+    pub exponent: u32,
+}
+
+const fn currency(code: &'static str, exponent: u32) -> Currency {
+    Currency { code, exponent }
+}
+
+/// Currencies prices may be given in.
+const CURRENCIES: &[Currency] = &[
+    currency("AED", 2),
+    currency("AUD", 2),
+    currency("BHD", 3),
+    currency("BRL", 2),
+    currency("CAD", 2),
+    currency("CHF", 2),
+    currency("CLP", 0),
+    currency("CNY", 2),
+    currency("CZK", 2),
+    currency("DKK", 2),
+    currency("EUR", 2),
+    currency("GBP", 2),
+    currency("HKD", 2),
+    currency("HUF", 2),
+    currency("IDR", 2),
+    currency("ILS", 2),
+    currency("INR", 2),
+    currency("ISK", 0),
+    currency("JOD", 3),
+    currency("JPY", 0),
+    currency("KRW", 0),
+    currency("KWD", 3),
+    currency("MXN", 2),
+    currency("MYR", 2),
+    currency("NOK", 2),
+    currency("NZD", 2),
+    currency("OMR", 3),
+    currency("PHP", 2),
+    currency("PLN", 2),
+    currency("RON", 2),
+    currency("SAR", 2),
+    currency("SEK", 2),
+    currency("SGD", 2),
+    currency("THB", 2),
+    currency("TRY", 2),
+    currency("TWD", 2),
+    currency("UAH", 2),
+    currency("USD", 2),
+    currency("VND", 0),
+    currency("ZAR", 2),
+];
+
+impl Currency {
+    /// Looks up a code case-insensitively; the result carries the canonical
+    /// upper-case code.
+    pub fn from_code(code: &str) -> Option<Self> {
+        CURRENCIES
+            .iter()
+            .find(|currency| currency.code.eq_ignore_ascii_case(code))
+            .copied()
+    }
+
+    /// Parses a decimal amount such as `19.99` into minor units.
+    pub fn parse_amount(&self, amount: &str) -> Result<i64, AmountError> {
+        parse_decimal(amount, self.exponent)
+    }
+
+    pub fn format_amount(&self, minor: i64) -> String {
+        if self.exponent == 0 {
+            return minor.to_string();
+        }
+        let scale = 10i64.pow(self.exponent);
+        format!(
+            "{}.{:0width$}",
+            minor / scale,
+            minor % scale,
+            width = self.exponent as usize
+        )
+    }
+}
+
+/// Parses a non-negative decimal with at most `exponent` significant decimal
+/// places into an integer scaled by `10^exponent`. Extra trailing zeros are
+/// fine; exponents, signs and separators are not.
+pub fn parse_decimal(amount: &str, exponent: u32) -> Result<i64, AmountError> {
+    let (whole, fraction) = match amount.split_once('.') {
+        Some((_, "")) => return Err(AmountError::Format),
+        Some((whole, fraction)) => (whole, fraction),
+        None => (amount, ""),
+    };
+    let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(AmountError::Format);
+    }
+    let (fraction, rest) = fraction.split_at(fraction.len().min(exponent as usize));
+    if rest.bytes().any(|byte| byte != b'0') {
+        return Err(AmountError::Precision(exponent));
+    }
+
+    // Checked after every digit, so long inputs fail on range instead of
+    // overflowing.
+    let limit = MAX_PRICE_MAJOR * 10u64.pow(exponent);
+    let padding = std::iter::repeat_n(b'0', exponent as usize - fraction.len());
+    let mut minor: u64 = 0;
+    for digit in whole.bytes().chain(fraction.bytes()).chain(padding) {
+        minor = minor * 10 + u64::from(digit - b'0');
+        if minor > limit {
+            return Err(AmountError::Range);
+        }
+    }
+    Ok(minor as i64)
+}
+
+/// `minor` formatted in `code`. Stored codes are always known; anything
+/// else is shown with two decimals rather than failing a read.
+pub fn format_price(minor: i64, code: &str) -> String {
+    Currency::from_code(code)
+        .unwrap_or(currency("", 2))
+        .format_amount(minor)
+}
+
+/// Reads a price as sent in product events. Events before schema version 7
+/// carried a JSON number in the currency's major unit.
+pub fn deserialize_price<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Price {
+        Decimal(String),
+        Legacy(f64),
+    }
+
+    Ok(match Price::deserialize(deserializer)? {
+        Price::Decimal(price) => price,
+        Price::Legacy(price) => format!("{:.2}", price),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd() -> Currency {
+        Currency::from_code("usd").unwrap()
+    }
+
+    #[test]
+    fn parses_decimals_into_minor_units() {
+        assert_eq!(usd().parse_amount("19.99"), Ok(1999));
+        assert_eq!(usd().parse_amount("19.990"), Ok(1999));
+        assert_eq!(usd().parse_amount("19.9"), Ok(1990));
+        assert_eq!(usd().parse_amount("19"), Ok(1900));
+        assert_eq!(usd().parse_amount("0"), Ok(0));
+        assert_eq!(usd().parse_amount("19.999"), Err(AmountError::Precision(2)));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        for amount in ["", ".", "1.", ".5", "-1", "+1", "1e3", "1,00", " 1", "1.2.3"] {
+            assert_eq!(usd().parse_amount(amount), Err(AmountError::Format), "{:?}", amount);
+        }
+    }
+
+    #[test]
+    fn enforces_the_price_ceiling() {
+        let max = MAX_PRICE_MAJOR.to_string();
+        assert_eq!(usd().parse_amount(&max), Ok(MAX_PRICE_MAJOR as i64 * 100));
+        assert_eq!(usd().parse_amount(&format!("{}.00", max)), Ok(MAX_PRICE_MAJOR as i64 * 100));
+        assert_eq!(usd().parse_amount(&format!("{}.01", max)), Err(AmountError::Range));
+        assert_eq!(
+            usd().parse_amount(&(MAX_PRICE_MAJOR + 1).to_string()),
+            Err(AmountError::Range)
+        );
+        assert_eq!(usd().parse_amount(&"9".repeat(40)), Err(AmountError::Range));
+    }
+
+    #[test]
+    fn follows_the_currency_exponent() {
+        let jpy = Currency::from_code("JPY").unwrap();
+        assert_eq!(jpy.parse_amount("1500"), Ok(1500));
+        assert_eq!(jpy.parse_amount("1500.0"), Ok(1500));
+        assert_eq!(jpy.parse_amount("1500.5"), Err(AmountError::Precision(0)));
+        assert_eq!(jpy.format_amount(1500), "1500");
+
+        let kwd = Currency::from_code("KWD").unwrap();
+        assert_eq!(kwd.parse_amount("1.234"), Ok(1234));
+        assert_eq!(kwd.parse_amount("1.2345"), Err(AmountError::Precision(3)));
+        assert_eq!(kwd.format_amount(1234), "1.234");
+        assert_eq!(kwd.format_amount(5), "0.005");
+    }
+
+    #[test]
+    fn formatted_amounts_parse_back() {
+        for code in ["JPY", "USD", "KWD"] {
+            let currency = Currency::from_code(code).unwrap();
+            let max = MAX_PRICE_MAJOR as i64 * 10i64.pow(currency.exponent);
+            for minor in [0, 1, 9, 10, 99, 100, 1999, 100_000, max] {
+                let formatted = currency.format_amount(minor);
+                assert_eq!(currency.parse_amount(&formatted), Ok(minor), "{} {}", code, formatted);
+            }
+        }
+        assert_eq!(usd().format_amount(1999), "19.99");
+        assert_eq!(usd().format_amount(7), "0.07");
+    }
+}
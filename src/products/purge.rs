@@ -0,0 +1,55 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::db::mongo::MongoError;
+use crate::db::repository::Repository;
+
+/// Background task that permanently removes products which have been in the
+/// trash for longer than the retention period.
+#[derive(Clone)]
+pub struct TrashPurger {
+    repo: Arc<dyn Repository>,
+    interval: Duration,
+    retention: chrono::Duration,
+}
+
+impl TrashPurger {
+    pub fn new(repo: Arc<dyn Repository>, config: &Config) -> Self {
+        Self {
+            repo,
+            interval: Duration::from_secs(config.product_purge_interval_secs),
+            // Periods too long to represent just mean nothing is ever purged.
+            retention: i64::try_from(config.product_trash_retention_days)
+                .ok()
+                .and_then(chrono::Duration::try_days)
+                .unwrap_or(chrono::Duration::MAX),
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                match self.run_once().await {
+                    Ok(0) => {}
+                    Ok(purged) => tracing::info!("Purged {} products from the trash", purged),
+                    Err(e) => tracing::error!("Failed to purge trashed products: {:?}", e),
+                }
+            }
+        })
+    }
+
+    /// Purges everything trashed before the retention cut-off and returns how
+    /// many products were removed.
+    pub async fn run_once(&self) -> Result<u64, MongoError> {
+        let cutoff = Utc::now()
+            .checked_sub_signed(self.retention)
+            .unwrap_or(chrono::DateTime::<Utc>::MIN_UTC);
+        self.repo.purge_trashed_products(cutoff).await
+    }
+}
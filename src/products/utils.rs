@@ -1,24 +1,221 @@
-use crate::kafka::producer::{AppKafkaProducer, ProductEvent};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use regex::{Regex, RegexBuilder};
+use serde::{Serialize, de::DeserializeOwned};
+use validator::ValidationError;
 
-use super::models::{Product, ProductResponse};
+use super::{
+  models::{Product, ProductResponse, SearchHighlight},
+  money::{AmountError, Currency, MAX_EXPONENT, format_price, parse_decimal},
+};
 
-pub async fn send_kafka_event<T: serde::Serialize + std::fmt::Debug + Clone + Send + 'static>(
-  kafka_producer: &AppKafkaProducer,
-  topic: &str,
-  event: ProductEvent<T>,
-) {
-  let producer = kafka_producer.clone();
-  let event_clone = event.clone();
-  let topic = topic.to_string();
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+const MAX_SKU_LENGTH: usize = 64;
 
-  tokio::spawn(async move {
-      match producer.send_product_event(&topic, event_clone).await {
-          Ok(_) => tracing::info!("Successfully sent Kafka event: {:?}", event.event_type),
-          Err(e) => tracing::error!("Failed to send Kafka event {:?}: {:?}", event.event_type, e),
-      }
-  });
+/// Columns of a CSV export. Imports read `sku`, `name`, `description`,
+/// `price` and `currency` and ignore the rest, so an export can be edited and uploaded again.
+const CSV_COLUMNS: [&str; 10] = [
+  "id",
+  "sku",
+  "name",
+  "description",
+  "price",
+  "currency",
+  "owner_id",
+  "created_at",
+  "updated_at",
+  "version",
+];
+
+#[derive(Serialize)]
+struct ProductCsvRow<'a> {
+  id: String,
+  sku: Option<&'a str>,
+  name: &'a str,
+  description: &'a str,
+  price: String,
+  currency: &'a str,
+  owner_id: Option<String>,
+  created_at: String,
+  updated_at: String,
+  version: i64,
+}
+
+fn csv_bytes(write: impl FnOnce(&mut csv::Writer<Vec<u8>>) -> csv::Result<()>) -> Vec<u8> {
+  let mut writer = csv::WriterBuilder::new()
+    .has_headers(false)
+    .from_writer(Vec::new());
+  write(&mut writer).expect("writing CSV to memory cannot fail");
+  writer.into_inner().expect("writing CSV to memory cannot fail")
+}
+
+pub fn product_csv_header() -> Vec<u8> {
+  csv_bytes(|writer| writer.write_record(CSV_COLUMNS))
+}
+
+pub fn product_csv_record(product: &Product) -> Vec<u8> {
+  let row = ProductCsvRow {
+    id: product._id.map(|id| id.to_hex()).unwrap_or_default(),
+    sku: product.sku.as_deref(),
+    name: &product.name,
+    description: &product.description,
+    price: format_price(product.price_minor, &product.currency),
+    currency: &product.currency,
+    owner_id: product.owner_id.map(|id| id.to_hex()),
+    created_at: product.created_at.to_rfc3339(),
+    updated_at: product.updated_at.to_rfc3339(),
+    version: product.version,
+  };
+  csv_bytes(|writer| writer.serialize(row))
+}
+
+/// The product as it appears in API responses, on a line of its own.
+pub fn product_json_line(product: &Product) -> Vec<u8> {
+  let mut line = serde_json::to_vec(&ProductResponse::from_product(product))
+    .expect("product responses are always valid JSON");
+  line.push(b'\n');
+  line
 }
 
 pub fn products_to_responses(products: &[Product]) -> Vec<ProductResponse> {
   products.iter().map(ProductResponse::from_product).collect()
-}
\ No newline at end of file
+}
+
+/// Strong entity tag for a product version.
+pub fn etag(version: i64) -> String {
+  format!("\"{}\"", version)
+}
+
+/// Whether an `If-Match` header value admits `version`: `*` or a list of
+/// tags, compared strongly, so weak tags never match.
+pub fn if_match_allows(if_match: &str, version: i64) -> bool {
+  let current = etag(version);
+  if_match
+    .split(',')
+    .map(str::trim)
+    .any(|tag| tag == "*" || tag == current)
+}
+
+pub fn encode_cursor<T: Serialize>(cursor: &T) -> String {
+  let bytes = bson::to_vec(cursor).expect("pagination cursors are always valid BSON");
+  URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(token: &str) -> Option<T> {
+  let bytes = URL_SAFE_NO_PAD.decode(token).ok()?;
+  bson::from_slice(&bytes).ok()
+}
+
+/// Individual words of a text query, ignoring quotes and negated terms.
+pub fn search_terms(query: &str) -> Vec<String> {
+  query
+    .split_whitespace()
+    .filter(|term| !term.starts_with('-'))
+    .map(|term| term.trim_matches('"').to_string())
+    .filter(|term| !term.is_empty())
+    .collect()
+}
+
+fn terms_regex(terms: &[String]) -> Option<Regex> {
+  if terms.is_empty() {
+    return None;
+  }
+  let pattern = terms
+    .iter()
+    .map(|term| regex::escape(term))
+    .collect::<Vec<_>>()
+    .join("|");
+  RegexBuilder::new(&pattern)
+    .case_insensitive(true)
+    .build()
+    .ok()
+}
+
+fn snippet(text: &str, matcher: &Regex) -> Option<String> {
+  let first = matcher.find(text)?;
+  let start = text[..first.start()]
+    .char_indices()
+    .rev()
+    .nth(SNIPPET_CONTEXT_CHARS - 1)
+    .map_or(0, |(index, _)| index);
+  let end = text[first.end()..]
+    .char_indices()
+    .nth(SNIPPET_CONTEXT_CHARS)
+    .map_or(text.len(), |(index, _)| first.end() + index);
+
+  let mut snippet = matcher
+    .replace_all(&text[start..end], "<em>$0</em>")
+    .into_owned();
+  if start > 0 {
+    snippet.insert_str(0, "...");
+  }
+  if end < text.len() {
+    snippet.push_str("...");
+  }
+  Some(snippet)
+}
+
+pub fn highlight_product(product: &Product, terms: &[String]) -> Vec<SearchHighlight> {
+  let Some(matcher) = terms_regex(terms) else {
+    return Vec::new();
+  };
+  [
+    ("name", &product.name),
+    ("description", &product.description),
+  ]
+  .into_iter()
+  .filter_map(|(field, text)| {
+    snippet(text, &matcher).map(|snippet| SearchHighlight {
+      field: field.to_string(),
+      snippet,
+    })
+  })
+  .collect()
+}
+
+fn validation_error(code: &'static str, message: &'static str) -> ValidationError {
+  ValidationError::new(code).with_message(message.into())
+}
+
+pub fn validate_not_blank(value: &str) -> Result<(), ValidationError> {
+  if value.trim().is_empty() {
+    return Err(validation_error("blank", "must not be blank"));
+  }
+  Ok(())
+}
+
+/// SKUs are 1 to 64 letters, digits, `.`, `_` or `-`, so they survive a trip
+/// through a spreadsheet unchanged.
+pub fn validate_sku(sku: &str) -> Result<(), ValidationError> {
+  if sku.is_empty() || sku.len() > MAX_SKU_LENGTH {
+    return Err(validation_error("length", "must be between 1 and 64 characters"));
+  }
+  if !sku
+    .chars()
+    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+  {
+    return Err(validation_error(
+      "format",
+      "may only contain letters, digits, '.', '_' and '-'",
+    ));
+  }
+  Ok(())
+}
+
+/// Prices are non-negative decimal strings. How many decimal places are
+/// allowed depends on the currency, which is checked once it is known.
+pub fn validate_price(price: &str) -> Result<(), ValidationError> {
+  parse_decimal(price, MAX_EXPONENT)
+    .map(|_| ())
+    .map_err(price_error)
+}
+
+pub fn price_error(error: AmountError) -> ValidationError {
+  ValidationError::new(error.code()).with_message(error.to_string().into())
+}
+
+pub fn validate_currency(code: &str) -> Result<(), ValidationError> {
+  if Currency::from_code(code).is_none() {
+    return Err(validation_error("currency", "must be a supported ISO 4217 currency code"));
+  }
+  Ok(())
+}
@@ -1,23 +1,70 @@
+use std::sync::Arc;
+
+use crate::auth::keys::{JwtKeyError, JwtKeys};
+use crate::auth::throttle::{LoginPolicy, LoginThrottle};
 use crate::config::Config;
+use crate::db::memory::InMemoryRepo;
 use crate::db::mongo::MongoRepo;
+use crate::db::repository::Repository;
+use crate::kafka::memory::InMemoryBroker;
 use crate::kafka::producer::AppKafkaProducer;
+use crate::kafka::publisher::EventPublisher;
+use crate::mail::mailer::{FileMailer, Mailer, MailerKind, RepositoryMailer};
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
-    pub db_repo: MongoRepo,
-    pub kafka_producer: AppKafkaProducer,
+    pub jwt_keys: Arc<JwtKeys>,
+    pub login_throttle: Arc<LoginThrottle>,
+    pub db_repo: Arc<dyn Repository>,
+    pub event_publisher: Arc<dyn EventPublisher>,
+    pub mailer: Arc<dyn Mailer>,
 }
 
 impl AppState {
     pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
         let db_repo = MongoRepo::init(&config.database_url, &config.database_name).await?;
-        let kafka_producer = AppKafkaProducer::new(&config.kafka_brokers)?;
+        if config.database_auto_migrate {
+            db_repo.migrate(&config).await?;
+        } else {
+            tracing::warn!("Automatic migrations are disabled, run `migrate` before serving");
+        }
+        let event_publisher: Arc<dyn EventPublisher> = if config.kafka_enabled {
+            Arc::new(AppKafkaProducer::new(&config.kafka_brokers)?)
+        } else {
+            tracing::warn!("Kafka is disabled, product events are kept in memory only");
+            Arc::new(InMemoryBroker::new())
+        };
+
+        Ok(Self::from_parts(config, Arc::new(db_repo), event_publisher)?)
+    }
 
+    pub fn in_memory(config: Config) -> Result<Self, JwtKeyError> {
+        Self::from_parts(
+            config,
+            Arc::new(InMemoryRepo::new()),
+            Arc::new(InMemoryBroker::new()),
+        )
+    }
+
+    pub fn from_parts(
+        config: Config,
+        db_repo: Arc<dyn Repository>,
+        event_publisher: Arc<dyn EventPublisher>,
+    ) -> Result<Self, JwtKeyError> {
+        let jwt_keys = Arc::new(JwtKeys::from_config(&config)?);
+        let login_throttle = Arc::new(LoginThrottle::new(LoginPolicy::from_config(&config)));
+        let mailer: Arc<dyn Mailer> = match config.mailer {
+            MailerKind::Mongo => Arc::new(RepositoryMailer::new(db_repo.clone())),
+            MailerKind::File => Arc::new(FileMailer::new(&config.mailer_file_path)),
+        };
         Ok(Self {
             config,
+            jwt_keys,
+            login_throttle,
             db_repo,
-            kafka_producer,
+            event_publisher,
+            mailer,
         })
     }
-}
\ No newline at end of file
+}
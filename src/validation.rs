@@ -0,0 +1,73 @@
+use axum::{
+    Json,
+    extract::{FromRequest, Request},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::AppError;
+
+/// One rule a request field failed.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct FieldError {
+    /// Path of the offending field, e.g. `price` or `items[2].name`
+    pub field: String,
+    /// Rule that failed, e.g. `length`, `range` or `precision`
+    pub code: String,
+    pub message: String,
+}
+
+/// Flattens nested validator errors into a sorted list of field errors.
+pub fn field_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut fields = Vec::new();
+    collect_field_errors("", errors, &mut fields);
+    fields.sort_by(|a, b| a.field.cmp(&b.field).then_with(|| a.code.cmp(&b.code)));
+    fields
+}
+
+fn collect_field_errors(prefix: &str, errors: &ValidationErrors, fields: &mut Vec<FieldError>) {
+    for (name, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                fields.extend(errors.iter().map(|error| FieldError {
+                    field: path.clone(),
+                    code: error.code.to_string(),
+                    message: error
+                        .message
+                        .as_ref()
+                        .map_or_else(|| format!("failed `{}` check", error.code), |m| m.to_string()),
+                }));
+            }
+            ValidationErrorsKind::Struct(nested) => collect_field_errors(&path, nested, fields),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_field_errors(&format!("{}[{}]", path, index), nested, fields);
+                }
+            }
+        }
+    }
+}
+
+/// JSON body extractor that also runs the payload's `Validate` rules, so
+/// handlers only ever see well-formed input.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        value.validate()?;
+        Ok(ValidatedJson(value))
+    }
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, Utc};
+use rs_kafka_mongo::auth::{
+    models::{AccountToken, Role, TokenPurpose},
+    utils::hash_token,
+};
+use serde_json::json;
+
+use common::{PASSWORD, TestApp, TestResponse};
+
+async fn log_in(app: &TestApp, username: &str, password: &str) -> TestResponse {
+    app.request(
+        Method::POST,
+        "/auth/login",
+        None,
+        Some(json!({ "username": username, "password": password })),
+    )
+    .await
+}
+
+async fn list_with_key(app: &TestApp, api_key: &str) -> StatusCode {
+    app.send(Method::GET, "/products", None, &[("x-api-key", api_key)], None)
+        .await
+        .status
+}
+
+#[tokio::test]
+async fn password_reset_revokes_access_tokens_and_api_keys() {
+    let app = TestApp::new();
+    let token = app.user("alice", Role::Editor).await;
+
+    let created = app
+        .request(
+            Method::POST,
+            "/auth/api-keys",
+            Some(&token),
+            Some(json!({ "name": "ci" })),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let api_key = created.body["key"].as_str().unwrap().to_string();
+    assert_eq!(list_with_key(&app, &api_key).await, StatusCode::OK);
+
+    let user = app
+        .state
+        .db_repo
+        .find_user_by_username("alice")
+        .await
+        .unwrap()
+        .unwrap();
+    let now = Utc::now();
+    app.state
+        .db_repo
+        .create_account_token(AccountToken {
+            _id: None,
+            user_id: user._id.unwrap(),
+            purpose: TokenPurpose::PasswordReset,
+            token_hash: hash_token("reset-token"),
+            email: None,
+            created_at: now,
+            expires_at: now + Duration::minutes(30),
+            used_at: None,
+        })
+        .await
+        .unwrap();
+    let reset = app
+        .request(
+            Method::POST,
+            "/auth/reset-password",
+            None,
+            Some(json!({ "token": "reset-token", "new_password": "newpassword1" })),
+        )
+        .await;
+    assert_eq!(reset.status, StatusCode::NO_CONTENT, "{}", reset.body);
+
+    let stale = app.request(Method::GET, "/products", Some(&token), None).await;
+    assert_eq!(stale.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(list_with_key(&app, &api_key).await, StatusCode::UNAUTHORIZED);
+
+    let old_password = log_in(&app, "alice", PASSWORD).await;
+    assert_eq!(old_password.status, StatusCode::UNAUTHORIZED);
+    let login = log_in(&app, "alice", "newpassword1").await;
+    assert_eq!(login.status, StatusCode::OK);
+    let fresh = login.body["token"].as_str().unwrap();
+    let listed = app.request(Method::GET, "/products", Some(fresh), None).await;
+    assert_eq!(listed.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn api_keys_cannot_update_the_profile() {
+    let app = TestApp::new();
+    let token = app.user("bob", Role::Editor).await;
+    let created = app
+        .request(
+            Method::POST,
+            "/auth/api-keys",
+            Some(&token),
+            Some(json!({ "name": "ci" })),
+        )
+        .await;
+    let api_key = created.body["key"].as_str().unwrap();
+
+    let with_key = app
+        .send(
+            Method::PATCH,
+            "/auth/me",
+            None,
+            &[("x-api-key", api_key)],
+            Some(json!({ "email": "attacker@example.com" })),
+        )
+        .await;
+    assert_eq!(with_key.status, StatusCode::FORBIDDEN);
+
+    let with_session = app
+        .request(
+            Method::PATCH,
+            "/auth/me",
+            Some(&token),
+            Some(json!({ "display_name": "Bob" })),
+        )
+        .await;
+    assert_eq!(with_session.status, StatusCode::OK, "{}", with_session.body);
+}
+
+#[tokio::test]
+async fn deleting_the_account_revokes_every_session() {
+    let app = TestApp::new();
+    let token = app.user("carol", Role::Editor).await;
+    let other = log_in(&app, "carol", PASSWORD).await;
+    assert_eq!(other.status, StatusCode::OK, "{}", other.body);
+    let other_token = other.body["token"].as_str().unwrap();
+
+    let deleted = app.request(Method::DELETE, "/auth/me", Some(&token), None).await;
+    assert_eq!(deleted.status, StatusCode::NO_CONTENT, "{}", deleted.body);
+
+    let listed = app.request(Method::GET, "/products", Some(other_token), None).await;
+    assert_eq!(listed.status, StatusCode::UNAUTHORIZED);
+    let created = app
+        .request(
+            Method::POST,
+            "/products",
+            Some(other_token),
+            Some(json!({ "name": "Orphan", "price": "1.00", "quantity": 1 })),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn password_change_revokes_sessions_but_keeps_api_keys() {
+    let app = TestApp::new();
+    let token = app.user("dave", Role::Editor).await;
+    let other = log_in(&app, "dave", PASSWORD).await;
+    let other_token = other.body["token"].as_str().unwrap();
+    let created = app
+        .request(
+            Method::POST,
+            "/auth/api-keys",
+            Some(&token),
+            Some(json!({ "name": "ci" })),
+        )
+        .await;
+    let api_key = created.body["key"].as_str().unwrap();
+
+    let changed = app
+        .request(
+            Method::POST,
+            "/auth/change-password",
+            Some(&token),
+            Some(json!({ "current_password": PASSWORD, "new_password": "newpassword1" })),
+        )
+        .await;
+    assert_eq!(changed.status, StatusCode::NO_CONTENT, "{}", changed.body);
+
+    for stale in [token.as_str(), other_token] {
+        let listed = app.request(Method::GET, "/products", Some(stale), None).await;
+        assert_eq!(listed.status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(list_with_key(&app, api_key).await, StatusCode::OK);
+
+    let login = log_in(&app, "dave", "newpassword1").await;
+    let fresh = login.body["token"].as_str().unwrap();
+    let listed = app.request(Method::GET, "/products", Some(fresh), None).await;
+    assert_eq!(listed.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn lockout_does_not_reveal_which_usernames_exist() {
+    let app = TestApp::new();
+    app.user("erin", Role::Viewer).await;
+    let max_failures = app.state.config.login_max_failures;
+
+    for username in ["erin", "nobody"] {
+        for _ in 0..max_failures {
+            let failed = log_in(&app, username, "wrong-password").await;
+            assert_eq!(failed.status, StatusCode::UNAUTHORIZED, "{}", username);
+        }
+        let locked = log_in(&app, username, "wrong-password").await;
+        assert_eq!(locked.status, StatusCode::TOO_MANY_REQUESTS, "{}", username);
+        assert_eq!(
+            locked.body["detail"],
+            "Account is temporarily locked after repeated failed logins"
+        );
+        assert!(locked.headers.contains_key("retry-after"));
+    }
+}
@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, Method, Request, StatusCode, header},
+};
+use rs_kafka_mongo::{
+    app::build_router,
+    auth::{
+        models::{Role, User},
+        utils::hash_password,
+    },
+    config::Config,
+    db::memory::InMemoryRepo,
+    kafka::memory::InMemoryBroker,
+    mail::mailer::MailerKind,
+    outbox::relay::OutboxRelay,
+    state::AppState,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+pub const PASSWORD: &str = "password123";
+
+pub fn test_config() -> Config {
+    Config {
+        server_addr: "127.0.0.1:0".to_string(),
+        database_url: String::new(),
+        database_name: String::new(),
+        database_auto_migrate: false,
+        kafka_brokers: String::new(),
+        kafka_enabled: false,
+        kafka_product_events_topic: "product_events".to_string(),
+        kafka_consumer_group: "test".to_string(),
+        kafka_consumer_max_retries: 0,
+        kafka_consumer_retry_backoff_ms: 0,
+        kafka_dead_letter_topic: "dead_letters".to_string(),
+        kafka_user_events_topic: "user_events".to_string(),
+        event_source: "test".to_string(),
+        jwt_algorithm: "HS256".parse().unwrap(),
+        jwt_secret: "test-secret".to_string(),
+        jwt_private_key_path: None,
+        jwt_verification_key_paths: Vec::new(),
+        access_token_ttl_minutes: 15,
+        refresh_token_ttl_days: 1,
+        signup_role: Role::Viewer,
+        login_max_failures: 5,
+        login_ip_max_failures: 100,
+        login_failure_window_secs: 60,
+        login_lockout_secs: 60,
+        login_delay_base_ms: 0,
+        trust_forwarded_for: false,
+        password_reset_ttl_minutes: 30,
+        email_verification_ttl_hours: 48,
+        mailer: MailerKind::Mongo,
+        mailer_file_path: String::new(),
+        mail_from: "no-reply@example.com".to_string(),
+        public_base_url: "http://localhost".to_string(),
+        bootstrap_admin_username: None,
+        bootstrap_admin_password: None,
+        outbox_poll_interval_ms: 1000,
+        outbox_batch_size: 100,
+        outbox_max_backoff_secs: 60,
+        product_trash_retention_days: 30,
+        product_purge_interval_secs: 3600,
+        product_import_max_bytes: 1024 * 1024,
+        default_currency: "USD".to_string(),
+    }
+}
+
+/// The full router over in-memory storage, with the broker it publishes to.
+pub struct TestApp {
+    pub state: AppState,
+    pub broker: InMemoryBroker,
+    router: Router,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let broker = InMemoryBroker::new();
+        let state = AppState::from_parts(
+            test_config(),
+            Arc::new(InMemoryRepo::new()),
+            Arc::new(broker.clone()),
+        )
+        .unwrap();
+        let (router, _) = build_router(state.clone());
+        Self {
+            state,
+            broker,
+            router,
+        }
+    }
+
+    /// Creates a user with `role` and returns an access token for them.
+    pub async fn user(&self, username: &str, role: Role) -> String {
+        let user = User::new(username.to_string(), hash_password(PASSWORD).unwrap(), role);
+        self.state.db_repo.create_user(user).await.unwrap();
+        let login = self
+            .request(
+                Method::POST,
+                "/auth/login",
+                None,
+                Some(serde_json::json!({ "username": username, "password": PASSWORD })),
+            )
+            .await;
+        assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+        login.body["token"].as_str().unwrap().to_string()
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> TestResponse {
+        self.send(method, uri, token, &[], body).await
+    }
+
+    pub async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = self
+            .router
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+        };
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    /// Publishes whatever the handlers left in the outbox.
+    pub async fn relay_outbox(&self) {
+        OutboxRelay::new(
+            self.state.db_repo.clone(),
+            self.state.event_publisher.clone(),
+            &self.state.config,
+        )
+        .run_once()
+        .await
+        .unwrap();
+    }
+}
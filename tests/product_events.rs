@@ -0,0 +1,101 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use rs_kafka_mongo::{
+    auth::models::Role,
+    kafka::producer::{PRODUCT_EVENT_SCHEMA_VERSION, ProductEvent, ProductEventType},
+    products::models::ProductResponse,
+};
+use serde_json::json;
+
+use common::TestApp;
+
+fn product_events(app: &TestApp) -> Vec<ProductEvent<ProductResponse>> {
+    app.broker
+        .decode(&app.state.config.kafka_product_events_topic)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn product_writes_publish_events() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+
+    let created = app
+        .request(
+            Method::POST,
+            "/products",
+            Some(&token),
+            Some(json!({ "name": "Pen", "price": "1.50", "sku": "PEN-1" })),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED);
+    let id = created.body["id"].as_str().unwrap().to_string();
+    let owner_id = created.body["owner_id"].as_str().unwrap().to_string();
+    let path = format!("/products/{}", id);
+
+    // Nothing reaches the broker until the relay reads the outbox.
+    assert!(product_events(&app).is_empty());
+    app.relay_outbox().await;
+    let events = product_events(&app);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.event_type, ProductEventType::Created);
+    assert_eq!(event.product_id, id);
+    assert_eq!(event.owner_id.as_deref(), Some(owner_id.as_str()));
+    assert_eq!(event.metadata.schema_version, PRODUCT_EVENT_SCHEMA_VERSION);
+    assert_eq!(event.metadata.actor_id.as_deref(), Some(owner_id.as_str()));
+    let payload = event.payload.as_ref().unwrap();
+    assert_eq!(payload.name, "Pen");
+    assert_eq!(payload.price, "1.50");
+    assert_eq!(payload.currency, "USD");
+    assert_eq!(payload.version, 1);
+
+    let updated = app
+        .request(Method::PATCH, &path, Some(&token), Some(json!({ "price": "2.25" })))
+        .await;
+    assert_eq!(updated.status, StatusCode::OK);
+    let deleted = app.request(Method::DELETE, &path, Some(&token), None).await;
+    assert_eq!(deleted.status, StatusCode::NO_CONTENT);
+    app.relay_outbox().await;
+
+    let events = product_events(&app);
+    let types: Vec<_> = events.iter().map(|event| event.event_type).collect();
+    assert_eq!(
+        types,
+        [
+            ProductEventType::Created,
+            ProductEventType::Updated,
+            ProductEventType::Deleted
+        ]
+    );
+    assert!(events.iter().all(|event| event.product_id == id));
+    let update = events[1].payload.as_ref().unwrap();
+    assert_eq!(update.price, "2.25");
+    assert_eq!(update.version, 2);
+
+    // Every record is keyed by product, so one product's events share a
+    // partition and stay in order.
+    let records = app
+        .broker
+        .published_to(&app.state.config.kafka_product_events_topic);
+    assert!(records.iter().all(|record| record.key == id));
+}
+
+#[tokio::test]
+async fn rejected_writes_publish_nothing() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+
+    let invalid = app
+        .request(
+            Method::POST,
+            "/products",
+            Some(&token),
+            Some(json!({ "name": "", "price": "1.50" })),
+        )
+        .await;
+    assert_eq!(invalid.status, StatusCode::UNPROCESSABLE_ENTITY);
+    app.relay_outbox().await;
+    assert!(product_events(&app).is_empty());
+}
@@ -0,0 +1,245 @@
+mod common;
+
+use axum::http::{Method, StatusCode, header};
+use rs_kafka_mongo::auth::models::Role;
+use serde_json::json;
+
+use common::TestApp;
+
+fn pen() -> serde_json::Value {
+    json!({ "name": "Pen", "description": "Blue ink", "price": "1.50" })
+}
+
+#[tokio::test]
+async fn create_get_list_update_delete() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+
+    let created = app
+        .request(Method::POST, "/products", Some(&token), Some(pen()))
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    assert_eq!(created.body["price"], "1.50");
+    assert_eq!(created.body["currency"], "USD");
+    assert_eq!(created.body["version"], 1);
+    let id = created.body["id"].as_str().unwrap().to_string();
+    let path = format!("/products/{}", id);
+
+    let fetched = app.request(Method::GET, &path, Some(&token), None).await;
+    assert_eq!(fetched.status, StatusCode::OK);
+    assert_eq!(fetched.body["name"], "Pen");
+    assert_eq!(fetched.headers[header::ETAG], "\"1\"");
+
+    let listed = app.request(Method::GET, "/products", Some(&token), None).await;
+    assert_eq!(listed.status, StatusCode::OK);
+    assert_eq!(listed.body["items"].as_array().unwrap().len(), 1);
+    assert_eq!(listed.body["items"][0]["id"], id.as_str());
+
+    let updated = app
+        .request(
+            Method::PATCH,
+            &path,
+            Some(&token),
+            Some(json!({ "name": "Fountain pen", "price": "12" })),
+        )
+        .await;
+    assert_eq!(updated.status, StatusCode::OK, "{}", updated.body);
+    assert_eq!(updated.body["name"], "Fountain pen");
+    assert_eq!(updated.body["price"], "12.00");
+    assert_eq!(updated.body["version"], 2);
+
+    let deleted = app.request(Method::DELETE, &path, Some(&token), None).await;
+    assert_eq!(deleted.status, StatusCode::NO_CONTENT);
+    let gone = app.request(Method::GET, &path, Some(&token), None).await;
+    assert_eq!(gone.status, StatusCode::NOT_FOUND);
+    let listed = app.request(Method::GET, "/products", Some(&token), None).await;
+    assert!(listed.body["items"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn list_filters_by_price_within_a_currency() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+    // Created out of price order, so sorting by price is not creation order.
+    let products = [
+        ("b", "5.00", "USD"),
+        ("c", "300", "JPY"),
+        ("a", "1.00", "USD"),
+        ("d", "3.00", "USD"),
+    ];
+    for (name, price, currency) in products {
+        let created = app
+            .request(
+                Method::POST,
+                "/products",
+                Some(&token),
+                Some(json!({ "name": name, "price": price, "currency": currency })),
+            )
+            .await;
+        assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    }
+
+    let names = |body: &serde_json::Value| -> Vec<String> {
+        body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let usd = app
+        .request(Method::GET, "/products?min_price=2", Some(&token), None)
+        .await;
+    assert_eq!(names(&usd.body), ["b", "d"]);
+    let jpy = app
+        .request(Method::GET, "/products?currency=JPY&max_price=500", Some(&token), None)
+        .await;
+    assert_eq!(names(&jpy.body), ["c"]);
+
+    // Sorting by price compares amounts in one currency only.
+    let by_price = app
+        .request(Method::GET, "/products?sort=price&order=desc", Some(&token), None)
+        .await;
+    assert_eq!(names(&by_price.body), ["b", "d", "a"]);
+    let by_price = app
+        .request(Method::GET, "/products?sort=price&currency=JPY", Some(&token), None)
+        .await;
+    assert_eq!(names(&by_price.body), ["c"]);
+}
+
+#[tokio::test]
+async fn requests_without_valid_credentials_are_rejected() {
+    let app = TestApp::new();
+
+    let anonymous = app.request(Method::GET, "/products", None, None).await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+    let forged = app
+        .request(Method::GET, "/products", Some("not-a-token"), None)
+        .await;
+    assert_eq!(forged.status, StatusCode::UNAUTHORIZED);
+
+    let viewer = app.user("viewer", Role::Viewer).await;
+    let denied = app
+        .request(Method::POST, "/products", Some(&viewer), Some(pen()))
+        .await;
+    assert_eq!(denied.status, StatusCode::FORBIDDEN);
+    assert_eq!(
+        denied.headers[header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+}
+
+#[tokio::test]
+async fn invalid_requests_are_rejected() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+
+    let malformed = app
+        .request(Method::POST, "/products", Some(&token), Some(json!({ "name": 1 })))
+        .await;
+    assert!(malformed.status.is_client_error());
+
+    let invalid = app
+        .request(
+            Method::POST,
+            "/products",
+            Some(&token),
+            Some(json!({ "name": "Pen", "price": "1.999" })),
+        )
+        .await;
+    assert_eq!(invalid.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(invalid.body["errors"][0]["field"], "price");
+    assert_eq!(invalid.body["errors"][0]["code"], "precision");
+
+    let bad_id = app
+        .request(Method::GET, "/products/not-an-id", Some(&token), None)
+        .await;
+    assert_eq!(bad_id.status, StatusCode::BAD_REQUEST);
+
+    let missing = app
+        .request(
+            Method::GET,
+            "/products/000000000000000000000000",
+            Some(&token),
+            None,
+        )
+        .await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+
+    let bad_range = app
+        .request(Method::GET, "/products?min_price=5&max_price=1", Some(&token), None)
+        .await;
+    assert_eq!(bad_range.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn only_the_owner_or_an_admin_may_modify() {
+    let app = TestApp::new();
+    let owner = app.user("owner", Role::Editor).await;
+    let other = app.user("other", Role::Editor).await;
+    let admin = app.user("admin", Role::Admin).await;
+
+    let created = app
+        .request(Method::POST, "/products", Some(&owner), Some(pen()))
+        .await;
+    let path = format!("/products/{}", created.body["id"].as_str().unwrap());
+
+    let refused = app
+        .request(Method::PATCH, &path, Some(&other), Some(json!({ "name": "Mine" })))
+        .await;
+    assert_eq!(refused.status, StatusCode::FORBIDDEN);
+    let refused = app.request(Method::DELETE, &path, Some(&other), None).await;
+    assert_eq!(refused.status, StatusCode::FORBIDDEN);
+
+    let allowed = app
+        .request(Method::PATCH, &path, Some(&admin), Some(json!({ "name": "Audited" })))
+        .await;
+    assert_eq!(allowed.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn stale_if_match_is_refused() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+    let created = app
+        .request(Method::POST, "/products", Some(&token), Some(pen()))
+        .await;
+    let path = format!("/products/{}", created.body["id"].as_str().unwrap());
+
+    let first = app
+        .send(
+            Method::PATCH,
+            &path,
+            Some(&token),
+            &[("if-match", "\"1\"")],
+            Some(json!({ "name": "First" })),
+        )
+        .await;
+    assert_eq!(first.status, StatusCode::OK);
+    let second = app
+        .send(
+            Method::PATCH,
+            &path,
+            Some(&token),
+            &[("if-match", "\"1\"")],
+            Some(json!({ "name": "Second" })),
+        )
+        .await;
+    assert_eq!(second.status, StatusCode::PRECONDITION_FAILED);
+}
+
+#[tokio::test]
+async fn duplicate_sku_is_a_conflict() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+    let product = json!({ "name": "Pen", "price": "1.50", "sku": "PEN-1" });
+
+    let first = app
+        .request(Method::POST, "/products", Some(&token), Some(product.clone()))
+        .await;
+    assert_eq!(first.status, StatusCode::CREATED);
+    let second = app
+        .request(Method::POST, "/products", Some(&token), Some(product))
+        .await;
+    assert_eq!(second.status, StatusCode::CONFLICT);
+}
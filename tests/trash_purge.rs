@@ -0,0 +1,33 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use rs_kafka_mongo::{auth::models::Role, products::purge::TrashPurger};
+use serde_json::json;
+
+use common::{TestApp, test_config};
+
+#[tokio::test]
+async fn retention_too_long_to_represent_purges_nothing() {
+    let app = TestApp::new();
+    let token = app.user("editor", Role::Editor).await;
+    let created = app
+        .request(
+            Method::POST,
+            "/products",
+            Some(&token),
+            Some(json!({ "name": "Pen", "price": "1.50" })),
+        )
+        .await;
+    let path = format!("/products/{}", created.body["id"].as_str().unwrap());
+    let deleted = app.request(Method::DELETE, &path, Some(&token), None).await;
+    assert_eq!(deleted.status, StatusCode::NO_CONTENT);
+
+    let mut config = test_config();
+    config.product_trash_retention_days = u64::MAX;
+    let purger = TrashPurger::new(app.state.db_repo.clone(), &config);
+    assert_eq!(purger.run_once().await.unwrap(), 0);
+
+    config.product_trash_retention_days = 0;
+    let purger = TrashPurger::new(app.state.db_repo.clone(), &config);
+    assert_eq!(purger.run_once().await.unwrap(), 1);
+}